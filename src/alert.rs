@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+
+use super::ws_coin::Price;
+
+/// A price level to be warned about when it's crossed
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Alert {
+    /// Name of the market, e.g. "BTC"
+    pub market: String,
+    /// Fire when the price rises above this
+    pub above: Option<Decimal>,
+    /// Fire when the price falls below this
+    pub below: Option<Decimal>,
+}
+
+/// Checks every price against the alerts, firing each time a level is crossed.
+pub struct Alerts {
+    alerts: Vec<Alert>,
+    last: HashMap<String, Decimal>, // previous price of each market
+}
+
+impl Alerts {
+    pub fn new(alerts: Vec<Alert>) -> Self {
+        Alerts {
+            alerts,
+            last: HashMap::new(),
+        }
+    }
+
+    /// Check against `alerts` from now on. The last prices are kept, so that a level the price is
+    /// already past doesn't fire.
+    pub fn set_alerts(&mut self, alerts: Vec<Alert>) {
+        self.alerts = alerts;
+    }
+
+    /// Messages of the alerts `price` fires. Nothing fires on the first price of a market, as
+    /// there's nothing it could have crossed from.
+    pub fn check(&mut self, price: &Price) -> Vec<String> {
+        let Some(last) = self.last.insert(price.name.clone(), price.price) else {
+            return Vec::new();
+        };
+        let p = price.price;
+        let mut fired = Vec::new();
+        for alert in self.alerts.iter().filter(|a| a.market == price.name) {
+            if let Some(above) = alert.above {
+                if last <= above && p > above {
+                    fired.push(format!("{} rose above {above}: {p}", price.name));
+                }
+            }
+            if let Some(below) = alert.below {
+                if last >= below && p < below {
+                    fired.push(format!("{} fell below {below}: {p}", price.name));
+                }
+            }
+        }
+        fired
+    }
+}
+
+#[test]
+fn test_alerts() {
+    use rust_decimal_macros::dec;
+
+    let mut alerts = Alerts::new(vec![Alert {
+        market: "BTC".to_string(),
+        above: Some(dec!(100000)),
+        below: Some(dec!(90000)),
+    }]);
+    let mut check = |name: &str, price| {
+        alerts.check(&Price {
+            symbol: format!("{name}USDT"),
+            name: name.to_string(),
+            price,
+            change_24h: None,
+        })
+    };
+    assert!(check("BTC", dec!(100500)).is_empty()); // already above on the first price
+    assert!(check("BTC", dec!(99999.9)).is_empty());
+    assert_eq!(
+        check("BTC", dec!(100000.1)),
+        ["BTC rose above 100000: 100000.1"]
+    );
+    assert!(check("BTC", dec!(100001)).is_empty()); // still above
+    assert!(check("ETH", dec!(1)).is_empty());
+    assert!(check("ETH", dec!(89999)).is_empty());
+    assert_eq!(check("BTC", dec!(89999)), ["BTC fell below 90000: 89999"]);
+
+    alerts.set_alerts(vec![Alert {
+        market: "BTC".to_string(),
+        above: None,
+        below: Some(dec!(95000)),
+    }]);
+    let mut check = |price| {
+        alerts.check(&Price {
+            symbol: "BTCUSDT".to_string(),
+            name: "BTC".to_string(),
+            price,
+            change_24h: None,
+        })
+    };
+    assert!(check(dec!(89000)).is_empty()); // was already below before the change
+    assert!(check(dec!(96000)).is_empty());
+    assert_eq!(check(dec!(94000)), ["BTC fell below 95000: 94000"]);
+}
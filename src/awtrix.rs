@@ -1,12 +1,54 @@
 use reqwest::{header, Client};
-use std::time::SystemTime;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use super::{feed::Frame, metrics::METRICS, screen::Screen, sink::DisplaySink};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Copy, Clone, Debug, PartialEq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// Legacy Awtrix 2 host, drawing over everything with `/api/v3/draw`
+    V2,
+    /// Awtrix 3 firmware (e.g. Ulanzi TC001), showing a custom app that rotates with the others
+    V3,
+}
+
+/// An Awtrix 3 custom app drawing `screen` as a bitmap, to post over HTTP or publish over MQTT
+pub fn custom_app(screen: &Screen) -> serde_json::Value {
+    let (height, width) = (screen.pixels.len(), screen.pixels[0].len());
+    let bitmap: Vec<u32> = screen
+        .pixels
+        .iter()
+        .flatten()
+        .map(|x| {
+            let [r, g, b] = x.map(|rgb888| rgb888.to_array()).unwrap_or_default();
+            (r as u32) << 16 | (g as u32) << 8 | b as u32
+        })
+        .collect();
+    serde_json::json!({
+        "draw": [{"db": [0, 0, width, height, bitmap]}],
+        // drop the app by itself if we stop updating it
+        "lifetime": 120,
+    })
+}
 
 pub struct Awtrix {
     host: String,
     port: u16,
     ssn: Client,
     min_interval: Option<u128>, // in milliseconds
-    last_sent_time: SystemTime,
+    protocol: Protocol,
+    app_name: String, // name of the custom app, for Awtrix 3
+    online: watch::Sender<bool>,
+    backoff: Duration,
+    // while the device is unreachable, frames are skipped until the given time, failing with the
+    // last error
+    retry: Option<(Instant, String)>,
+    size: (usize, usize), // of the last frame, to clear as much
 }
 
 impl Awtrix {
@@ -14,57 +56,364 @@ impl Awtrix {
         Awtrix {
             host,
             port,
-            ssn: Client::new(),
+            ssn: Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("Failed to build http client"),
             min_interval,
-            last_sent_time: SystemTime::now(),
+            protocol: Protocol::V2,
+            app_name: "cyberpunk".to_string(),
+            online: watch::Sender::new(true),
+            backoff: MIN_BACKOFF,
+            retry: None,
+            size: Screen::default().size(),
         }
     }
 
-    async fn push(&self, data: serde_json::Value, endpoint: &str) {
-        let _ = self
+    /// Talk to the device with `protocol`; for Awtrix 3, frames are shown as the app `app_name`.
+    pub fn with_protocol(mut self, protocol: Protocol, app_name: String) -> Self {
+        self.protocol = protocol;
+        self.app_name = app_name;
+        self
+    }
+
+    /// Whether the last frame reached the device
+    pub fn online(&self) -> watch::Receiver<bool> {
+        self.online.subscribe()
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("http://{}:{}/api/{}", self.host, self.port, path)
+    }
+
+    async fn push(&self, path: &str, query: &[(&str, &str)], body: String) -> anyhow::Result<()> {
+        let started = Instant::now();
+        let result = self.try_push(path, query, body).await;
+        let host = format!("{}:{}", self.host, self.port);
+        METRICS.awtrix_request(&host, started.elapsed(), result.is_ok());
+        result
+    }
+
+    async fn try_push(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+        body: String,
+    ) -> anyhow::Result<()> {
+        let resp = self
             .ssn
-            .post(format!(
-                "http://{}:{}/api/v3/{}",
-                self.host, self.port, endpoint
-            ))
-            .body(serde_json::to_string(&data).unwrap())
+            .post(self.url(path))
+            .query(query)
+            .body(body)
             .header(header::CONTENT_TYPE, "application/json")
             .send()
-            .await;
+            .await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            anyhow::bail!("/api/{path} returned {status}: {}", body.trim());
+        }
+        Ok(())
+    }
+
+    async fn push_json(&self, path: &str, data: serde_json::Value) -> anyhow::Result<()> {
+        self.push(path, &[], serde_json::to_string(&data).unwrap())
+            .await
+    }
+
+    /// Stop drawing and give the clock back: leave custom-draw mode on Awtrix 2, remove the
+    /// custom app on Awtrix 3.
+    pub async fn exit(&self) -> anyhow::Result<()> {
+        match self.protocol {
+            Protocol::V2 => {
+                self.push_json(
+                    "v3/draw",
+                    serde_json::json!({
+                        "draw": [{"type": "exit"}],
+                    }),
+                )
+                .await
+            }
+            // an empty payload deletes the app
+            Protocol::V3 => {
+                self.push("custom", &[("name", &self.app_name)], String::new())
+                    .await
+            }
+        }
     }
 
-    pub async fn exit(&self) {
-        self.push(
-            serde_json::json!({
-                "draw": [{"type": "exit"}],
-            }),
-            "draw",
+    /// Send `screen` to the device.
+    ///
+    /// After a failure, frames are dropped with exponential backoff until the device answers
+    /// again, so an unreachable clock doesn't hold up every frame for the request timeout. The
+    /// dropped frames fail with the error of the last attempt.
+    pub async fn plot(&mut self, screen: &Screen) -> anyhow::Result<()> {
+        if let Some((at, error)) = &self.retry {
+            if Instant::now() < *at {
+                anyhow::bail!("{error}");
+            }
+        }
+        let result = match self.protocol {
+            Protocol::V2 => {
+                self.push_json(
+                    "v3/draw",
+                    serde_json::json!({
+                        "draw": [
+                            {
+                                "type": "bmp",
+                                "position": [0, 0],
+                                "size": [screen.width(), screen.height()],
+                                "data": screen.serialize(),
+                            },
+                            {"type": "show"},
+                        ],
+                    }),
+                )
+                .await
+            }
+            Protocol::V3 => {
+                self.push(
+                    "custom",
+                    &[("name", &self.app_name)],
+                    custom_app(screen).to_string(),
+                )
+                .await
+            }
+        };
+
+        match &result {
+            Ok(()) => {
+                self.online.send_replace(true);
+                self.backoff = MIN_BACKOFF;
+                self.retry = None;
+            }
+            Err(e) => {
+                self.online.send_replace(false);
+                self.retry = Some((Instant::now() + self.backoff, format!("{e:#}")));
+                self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+        result
+    }
+
+    /// Fail on Awtrix 2, which has no endpoint for `what`
+    fn ensure_v3(&self, what: &str) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.protocol == Protocol::V3,
+            "{what} is unsupported on Awtrix 2"
+        );
+        Ok(())
+    }
+
+    /// Show `text` once as a notification, on top of the running apps (Awtrix 3 only).
+    pub async fn notify(&self, text: &str) -> anyhow::Result<()> {
+        self.ensure_v3("notify")?;
+        self.push_json("notify", serde_json::json!({ "text": text }))
+            .await
+    }
+
+    /// Set the matrix brightness, from 0 to 255, turning off auto-brightness (Awtrix 3 only).
+    pub async fn set_brightness(&self, brightness: u8) -> anyhow::Result<()> {
+        self.ensure_v3("brightness")?;
+        self.push_json(
+            "settings",
+            serde_json::json!({ "ABRI": false, "BRI": brightness }),
         )
         .await
     }
 
-    pub async fn plot(&mut self, rgb565: &[u16]) {
-        if let Some(min_interval) = self.min_interval {
-            if self.last_sent_time.elapsed().unwrap().as_millis() < min_interval {
-                // 小于0.1秒的间隔没有必要发送，人眼无法分辨
-                return;
-            }
+    /// Device statistics such as battery, light level, brightness and uptime (Awtrix 3 only).
+    pub async fn stats(&self) -> anyhow::Result<serde_json::Value> {
+        self.ensure_v3("stats")?;
+        let body = self
+            .ssn
+            .get(self.url("stats"))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+}
+
+impl DisplaySink for Awtrix {
+    fn name(&self) -> String {
+        format!("Awtrix at {}:{}", self.host, self.port)
+    }
+
+    fn min_interval(&self) -> Duration {
+        Duration::from_millis(self.min_interval.unwrap_or_default() as u64)
+    }
+
+    async fn render(&mut self, frame: &Frame) -> anyhow::Result<()> {
+        self.size = frame.screen.size();
+        self.plot(&frame.screen).await
+    }
+
+    async fn set_brightness(&mut self, brightness: u8) -> anyhow::Result<()> {
+        match self.protocol {
+            Protocol::V2 => Ok(()), // no brightness setting on Awtrix 2
+            Protocol::V3 => Awtrix::set_brightness(self, brightness).await,
         }
-        self.push(
-            serde_json::json!({
-                "draw": [
-                    {
-                        "type": "bmp",
-                        "position": [0, 0],
-                        "size": [32, 8],
-                        "data": rgb565,
-                    },
-                    {"type": "show"},
-                ],
-            }),
-            "draw",
+    }
+
+    async fn alert(&mut self, message: &str) -> anyhow::Result<()> {
+        match self.protocol {
+            Protocol::V2 => Ok(()), // no notifications on Awtrix 2
+            Protocol::V3 => self.notify(message).await,
+        }
+    }
+
+    async fn clear(&mut self) -> anyhow::Result<()> {
+        let (width, height) = self.size;
+        self.plot(&Screen::new(width, height)).await
+    }
+
+    async fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.exit().await?;
+        log::info!("Released {}", self.name());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_awtrix3() {
+    use super::screen::Rgb888;
+    use axum::{extract::State, http::Uri, routing::any};
+    use std::sync::{Arc, Mutex};
+
+    // a stand-in for the clock, recording every request as (path and query, body)
+    type Requests = Arc<Mutex<Vec<(String, String)>>>;
+    let requests = Requests::default();
+    let app = axum::Router::new()
+        .route(
+            "/api/stats",
+            any(|| async { axum::Json(serde_json::json!({"bri": 120, "uptime": 42})) }),
         )
-        .await;
-        self.last_sent_time = SystemTime::now();
+        .fallback(any(
+            |State(requests): State<Requests>, uri: Uri, body: String| async move {
+                requests.lock().unwrap().push((uri.to_string(), body));
+                "OK"
+            },
+        ))
+        .with_state(requests.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let mut awtrix = Awtrix::new("127.0.0.1".to_string(), port, None)
+        .with_protocol(Protocol::V3, "btc price".to_string());
+    let mut screen = Screen::default();
+    screen.draw(&[vec![Some(Rgb888::new(255, 0, 1))]], 1, 0);
+    awtrix.plot(&screen).await.unwrap();
+    awtrix.notify("hello").await.unwrap();
+    awtrix.set_brightness(30).await.unwrap();
+    assert_eq!(awtrix.stats().await.unwrap()["uptime"], 42);
+    awtrix.exit().await.unwrap();
+
+    let requests = requests.lock().unwrap();
+    let paths: Vec<&str> = requests.iter().map(|(path, _)| path.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "/api/custom?name=btc+price",
+            "/api/notify",
+            "/api/settings",
+            "/api/custom?name=btc+price",
+        ]
+    );
+
+    let app: serde_json::Value = serde_json::from_str(&requests[0].1).unwrap();
+    let db = &app["draw"][0]["db"];
+    assert_eq!(db.as_array().unwrap()[..4], [0, 0, 32, 8]);
+    assert_eq!(db[4].as_array().unwrap().len(), 32 * 8);
+    assert_eq!(db[4][0], 0);
+    assert_eq!(db[4][1], 0xFF0001);
+
+    assert_eq!(requests[1].1, r#"{"text":"hello"}"#);
+    assert_eq!(requests[2].1, r#"{"ABRI":false,"BRI":30}"#);
+    assert_eq!(requests[3].1, "");
+}
+
+#[tokio::test]
+async fn test_awtrix_backoff() {
+    use axum::{extract::State, http::StatusCode, routing::any};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // a clock that is still booting, failing every request
+    let hits = Arc::new(AtomicUsize::new(0));
+    let app = axum::Router::new()
+        .fallback(any(|State(hits): State<Arc<AtomicUsize>>| async move {
+            hits.fetch_add(1, Ordering::SeqCst);
+            (StatusCode::SERVICE_UNAVAILABLE, "booting\n")
+        }))
+        .with_state(hits.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let mut awtrix = Awtrix::new("127.0.0.1".to_string(), port, None);
+    let online = awtrix.online();
+    let err = awtrix.plot(&Screen::default()).await.unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "/api/v3/draw returned 503 Service Unavailable: booting"
+    );
+    assert!(!*online.borrow());
+
+    // backing off: the next frame isn't even sent
+    let again = awtrix.plot(&Screen::default()).await.unwrap_err();
+    assert_eq!(again.to_string(), err.to_string());
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_awtrix2_draw() {
+    use super::ws_coin::ConnectionState;
+    use axum::{extract::State, routing::any};
+    use std::sync::{Arc, Mutex};
+
+    // a stand-in for the host, recording the body of every request
+    let bodies = Arc::new(Mutex::new(Vec::new()));
+    let app = axum::Router::new()
+        .fallback(any(
+            |State(bodies): State<Arc<Mutex<Vec<String>>>>, body: String| async move {
+                bodies.lock().unwrap().push(body);
+                "OK"
+            },
+        ))
+        .with_state(bodies.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let mut awtrix = Awtrix::new("127.0.0.1".to_string(), port, None);
+    let frame = Frame {
+        screen: Screen::new(16, 4),
+        price: None,
+        new_price: false,
+        connection: ConnectionState::Connected,
+    };
+    awtrix.render(&frame).await.unwrap();
+    awtrix.clear().await.unwrap();
+
+    // both drawn at the size of the layout, rather than the 32x8 of the clock
+    for body in bodies.lock().unwrap().iter() {
+        let draw: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(draw["draw"][0]["size"], serde_json::json!([16, 4]));
     }
+    assert_eq!(bodies.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_awtrix2_unsupported() {
+    // nothing listens there: no request may be sent
+    let mut awtrix = Awtrix::new("127.0.0.1".to_string(), 1, None);
+    let err = awtrix.notify("hello").await.unwrap_err();
+    assert_eq!(err.to_string(), "notify is unsupported on Awtrix 2");
+    assert!(awtrix.stats().await.is_err());
+    DisplaySink::set_brightness(&mut awtrix, 30).await.unwrap();
+    awtrix.alert("hello").await.unwrap();
 }
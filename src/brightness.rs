@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use chrono::NaiveTime;
+use serde::{Deserialize, Deserializer};
+use tokio::sync::watch;
+
+use super::sink::Dimmer;
+
+/// How often the schedule is checked, which also paces the ramps
+const TICK: Duration = Duration::from_secs(1);
+
+/// A brightness level of the schedule
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    /// From 0 to 255, see [`DisplaySink::set_brightness`]
+    ///
+    /// [`DisplaySink::set_brightness`]: crate::sink::DisplaySink::set_brightness
+    On(u8),
+    /// Blank the displays, the feed going on
+    Off,
+}
+
+impl Level {
+    fn value(self) -> f64 {
+        match self {
+            Level::On(level) => level as f64,
+            Level::Off => 0.,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Level {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Level(u8),
+            Name(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Level(level) => Ok(Level::On(level)),
+            Raw::Name(name) if name == "off" => Ok(Level::Off),
+            Raw::Name(name) => Err(serde::de::Error::custom(format!(
+                "invalid level {name:?}, expected 0 to 255 or \"off\""
+            ))),
+        }
+    }
+}
+
+/// A level, from a time of day until the next one
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Period {
+    #[serde(deserialize_with = "crate::config::deserialize_time_of_day")]
+    pub from: NaiveTime,
+    pub level: Level,
+}
+
+/// What the devices with a dimmer are set to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Setting {
+    pub level: u8,
+    pub blanked: bool,
+}
+
+/// Dims the nixie tubes and Awtrix by the time of day, e.g. full from 08:00, low from 20:00, and
+/// off from 01:00 to 06:00.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrightnessSchedule {
+    /// How long the change from one level to the next takes
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub ramp: Duration,
+    pub schedule: Vec<Period>,
+}
+
+impl Default for BrightnessSchedule {
+    fn default() -> Self {
+        BrightnessSchedule {
+            ramp: Duration::from_secs(10 * 60),
+            schedule: Vec::new(),
+        }
+    }
+}
+
+impl BrightnessSchedule {
+    /// The setting at `now`, if there's a schedule at all
+    ///
+    /// Each level is ramped to from the previous one over `ramp`. Going off, the displays are
+    /// dimmed down to 0 first, and blanked at the end of the ramp; coming back on, they start
+    /// from 0.
+    pub fn setting_at(&self, now: NaiveTime) -> Option<Setting> {
+        let mut periods: Vec<&Period> = self.schedule.iter().collect();
+        periods.sort_by_key(|p| p.from);
+        // the period started last, or yesterday's last one
+        let current = periods
+            .iter()
+            .rposition(|p| p.from <= now)
+            .unwrap_or(periods.len().checked_sub(1)?);
+        let previous = periods[(current + periods.len() - 1) % periods.len()];
+        let current = periods[current];
+
+        let since = (now - current.from)
+            .num_milliseconds()
+            .rem_euclid(86_400_000) as f64;
+        let ramped = match self.ramp.as_millis() {
+            0 => 1.,
+            ramp => (since / ramp as f64).min(1.),
+        };
+        let (from, to) = (previous.level.value(), current.level.value());
+        Some(Setting {
+            level: (from + (to - from) * ramped).round() as u8,
+            blanked: current.level == Level::Off && ramped == 1.,
+        })
+    }
+
+    /// Apply the latest `schedule` with `dimmer` for ever. It's only set on changes, so that a
+    /// brightness set meanwhile, e.g. with the control API, holds until the next one.
+    pub async fn run(mut schedule: watch::Receiver<Self>, dimmer: Dimmer) {
+        let mut applied: Option<Setting> = None;
+        let mut tick = tokio::time::interval(TICK);
+        let mut reloading = true;
+        loop {
+            tokio::select! {
+                _ = tick.tick() => {}
+                changed = schedule.changed(), if reloading => reloading = changed.is_ok(),
+            }
+            let Some(setting) = schedule.borrow().setting_at(chrono::Local::now().time()) else {
+                continue;
+            };
+            if applied.map(|a| a.level) != Some(setting.level) {
+                dimmer.set(setting.level);
+            }
+            if applied.map(|a| a.blanked) != Some(setting.blanked) {
+                log::info!(
+                    "{} the displays on schedule",
+                    if setting.blanked {
+                        "Blanking"
+                    } else {
+                        "Turning on"
+                    }
+                );
+                dimmer.set_blanked(setting.blanked);
+            }
+            applied = Some(setting);
+        }
+    }
+}
+
+#[test]
+fn test_brightness_schedule() {
+    let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+    let period = |from, level| Period { from, level };
+    let schedule = BrightnessSchedule {
+        ramp: Duration::from_secs(10 * 60),
+        schedule: vec![
+            period(at(20, 0), Level::On(64)),
+            period(at(8, 0), Level::On(255)),
+            period(at(1, 0), Level::Off),
+            period(at(6, 0), Level::On(64)),
+        ],
+    };
+    let setting = |h, m| schedule.setting_at(at(h, m)).unwrap();
+    let on = |level| Setting {
+        level,
+        blanked: false,
+    };
+
+    assert_eq!(setting(12, 0), on(255));
+    assert_eq!(setting(20, 5), on(160)); // halfway down to 64
+    assert_eq!(setting(23, 0), on(64));
+    assert_eq!(setting(0, 30), on(64)); // still yesterday's evening
+    assert_eq!(setting(1, 5), on(32)); // dimming down before blanking
+    assert_eq!(
+        setting(3, 0),
+        Setting {
+            level: 0,
+            blanked: true
+        }
+    );
+    assert_eq!(setting(6, 0), on(0));
+    assert_eq!(setting(7, 59), on(64));
+
+    assert_eq!(BrightnessSchedule::default().setting_at(at(12, 0)), None);
+    let instant = BrightnessSchedule {
+        ramp: Duration::ZERO,
+        ..schedule
+    };
+    assert_eq!(instant.setting_at(at(20, 0)), Some(on(64)));
+}
@@ -0,0 +1,506 @@
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Context as _;
+use chrono::NaiveTime;
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use super::{
+    alert::Alert,
+    awtrix::Protocol,
+    brightness::BrightnessSchedule,
+    matrix::Layout,
+    nixie::{
+        CathodeCycle, Dots, Heartbeat, Mode as NixieMode, NumberFormat, Protocol as NixieProtocol,
+        ProtocolKind, Schedule,
+    },
+    output::Output,
+    screen::{palette::Palette, term_style::TermStyle},
+    ws_coin::{Market, Source},
+};
+
+/// Everything that can be set in the `--config` TOML file. Command line flags override it.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub source: Source,
+    /// Markets to follow, the first one being shown on the matrix
+    pub markets: Vec<Market>,
+    pub layout: Layout,
+    pub colors: Palette,
+    pub alerts: Vec<Alert>,
+    pub terminal: TerminalConfig,
+    pub awtrix: AwtrixConfig,
+    pub web: WebConfig,
+    pub mqtt: MqttConfig,
+    pub nixie: NixieConfig,
+    pub run: RunConfig,
+    pub control: ControlConfig,
+    pub metrics: MetricsConfig,
+    pub brightness: BrightnessSchedule,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            source: Source::default(),
+            markets: vec![Market {
+                symbol: "BTCUSDT".to_string(),
+                name: "BTC".to_string(),
+            }],
+            layout: Layout::default(),
+            colors: Palette::default(),
+            alerts: Vec::new(),
+            terminal: TerminalConfig::default(),
+            awtrix: AwtrixConfig::default(),
+            web: WebConfig::default(),
+            mqtt: MqttConfig::default(),
+            nixie: NixieConfig::default(),
+            run: RunConfig::default(),
+            control: ControlConfig::default(),
+            metrics: MetricsConfig::default(),
+            brightness: BrightnessSchedule::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TerminalConfig {
+    /// `<glyphs>[:<colors>]`, glyphs possibly "auto", see `matrix --help`
+    pub style: TermStyle,
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        TerminalConfig {
+            style: TermStyle::detect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AwtrixConfig {
+    pub host: String,
+    pub port: u16,
+    /// Minimum interval between updates
+    #[serde(deserialize_with = "deserialize_optional_duration")]
+    pub min_interval: Option<Duration>,
+    pub protocol: Protocol,
+    /// Name of the custom app showing the matrix, on Awtrix 3
+    pub app_name: String,
+}
+
+impl Default for AwtrixConfig {
+    fn default() -> Self {
+        AwtrixConfig {
+            host: "localhost".to_string(),
+            port: 7000,
+            min_interval: None,
+            protocol: Protocol::V2,
+            app_name: "cyberpunk".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebConfig {
+    pub host: IpAddr,
+    pub port: u16,
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        WebConfig {
+            host: [127, 0, 0, 1].into(),
+            port: 8080,
+        }
+    }
+}
+
+/// A password, kept out of the logs
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(pub String);
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "***")
+    }
+}
+
+impl std::str::FromStr for Secret {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Secret(s.to_string()))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<Secret>,
+    pub client_id: String,
+    /// MQTT prefix of the Awtrix 3 clock
+    pub awtrix_prefix: String,
+    /// Name of the custom app showing the matrix on the clock
+    pub app_name: String,
+    /// Prefix of the price topics, e.g. `<topic_prefix>/btc/price`
+    pub topic_prefix: String,
+    /// Announce the price sensors to Home Assistant
+    pub discovery: bool,
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        MqttConfig {
+            host: "localhost".to_string(),
+            port: 1883,
+            username: None,
+            password: None,
+            client_id: "cyberpunk_display".to_string(),
+            awtrix_prefix: "awtrix".to_string(),
+            app_name: "cyberpunk".to_string(),
+            topic_prefix: "cyberpunk_display".to_string(),
+            discovery: true,
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NixieConfig {
+    pub serial_port: String,
+    /// From 0 to 8
+    pub brightness: u8,
+    /// What the board on the port understands, "timd" or "noritake"
+    pub protocol: ProtocolKind,
+    /// How many tubes the board has, or digits the VFD shows
+    pub tubes: usize,
+    /// Where the decimal points sit on the tubes, "left", "right" or "none"
+    pub dots: Dots,
+    /// How the price is laid out on the tubes
+    pub format: NumberFormat,
+    /// What the first decimal point tells: "blink" on every new price, "direction" lit while the
+    /// price goes up, or "none"
+    pub heartbeat: Heartbeat,
+    /// Light the LEDs under the tubes in the `up`, `down` or `flat` color of `[colors]`, by where
+    /// the price goes
+    pub underlight: bool,
+    /// Show the price, the time, or alternate between them
+    pub mode: NixieMode,
+    /// How long the price is shown in each cycle of the "alternate" mode, or at the start of each
+    /// minute in the "clock" mode
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub price_duration: Duration,
+    /// How long the time is shown in each cycle of the "alternate" mode
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub time_duration: Duration,
+    pub cathode_cycle: CathodeCycle,
+}
+
+impl NixieConfig {
+    /// `brightness` on the 0 to 255 scale of [`DisplaySink::set_brightness`]
+    ///
+    /// [`DisplaySink::set_brightness`]: crate::sink::DisplaySink::set_brightness
+    pub fn dimmer_level(&self) -> u8 {
+        (self.brightness as u16 * 255 / 8).min(255) as u8
+    }
+
+    pub fn protocol(&self) -> Box<dyn NixieProtocol> {
+        self.protocol.protocol(self.tubes, self.dots)
+    }
+
+    pub fn schedule(&self) -> Schedule {
+        Schedule {
+            mode: self.mode,
+            price_for: self.price_duration,
+            time_for: self.time_duration,
+        }
+    }
+}
+
+impl Default for NixieConfig {
+    fn default() -> Self {
+        let schedule = Schedule::default();
+        NixieConfig {
+            serial_port: "/dev/ttyUSB0".to_string(),
+            brightness: 8,
+            protocol: ProtocolKind::Timd,
+            tubes: 6,
+            dots: Dots::Left,
+            format: NumberFormat::default(),
+            heartbeat: Heartbeat::Blink,
+            underlight: false,
+            mode: schedule.mode,
+            price_duration: schedule.price_for,
+            time_duration: schedule.time_for,
+            cathode_cycle: CathodeCycle::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunConfig {
+    /// Outputs of the `run` subcommand, as given to `--output`
+    pub outputs: Vec<Output>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlConfig {
+    /// Where to serve the control API, e.g. "127.0.0.1:8081"; not served if unset
+    pub address: Option<std::net::SocketAddr>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Where to serve Prometheus metrics on `/metrics`, e.g. "0.0.0.0:9100"; not served if unset
+    pub address: Option<std::net::SocketAddr>,
+}
+
+impl Config {
+    /// Read and validate the config file at `path`
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config = Self::parse(&text).with_context(|| format!("Invalid {}", path.display()))?;
+        config
+            .validate()
+            .with_context(|| format!("Invalid {}", path.display()))?;
+        Ok(config)
+    }
+
+    /// Parse a config file, naming the offending key on errors
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        serde_path_to_error::deserialize(toml::Deserializer::new(text)).map_err(|e| {
+            let key = e.path().to_string();
+            let e = e.into_inner();
+            let line = e
+                .span()
+                .map(|span| text[..span.start].matches('\n').count() + 1);
+            match (key.as_str(), line) {
+                // a syntax error, not a bad value: the message points at it already
+                (".", _) => anyhow::anyhow!("{e}"),
+                (_, Some(line)) => anyhow::anyhow!("`{key}` (line {line}): {}", e.message()),
+                (_, None) => anyhow::anyhow!("`{key}`: {}", e.message()),
+            }
+        })
+    }
+
+    /// Check what can't be told from the types alone. Run again after command line flags are
+    /// applied.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match url::Url::parse(&self.source.url) {
+            Ok(url) if ["ws", "wss"].contains(&url.scheme()) => {}
+            Ok(_) => anyhow::bail!("`source.url`: expected a ws:// or wss:// URL"),
+            Err(e) => anyhow::bail!("`source.url`: {e}"),
+        }
+        anyhow::ensure!(!self.markets.is_empty(), "`markets`: no market to show");
+        for (i, market) in self.markets.iter().enumerate() {
+            anyhow::ensure!(
+                !market.symbol.is_empty(),
+                "`markets[{i}].symbol`: must not be empty"
+            );
+            anyhow::ensure!(
+                !self.markets[..i].iter().any(|m| m.name == market.name),
+                "`markets[{i}].name`: {:?} is used twice",
+                market.name
+            );
+        }
+        if let Err(e) = self.layout.validate() {
+            anyhow::bail!("`layout`: {e}");
+        }
+        for (i, alert) in self.alerts.iter().enumerate() {
+            anyhow::ensure!(
+                self.markets.iter().any(|m| m.name == alert.market),
+                "`alerts[{i}].market`: unknown market {:?}",
+                alert.market
+            );
+            anyhow::ensure!(
+                alert.above.is_some() || alert.below.is_some(),
+                "`alerts[{i}]`: set `above`, `below` or both"
+            );
+        }
+        anyhow::ensure!(
+            self.nixie.brightness <= 8,
+            "`nixie.brightness`: must be between 0 and 8"
+        );
+        anyhow::ensure!(
+            (4..=16).contains(&self.nixie.tubes),
+            "`nixie.tubes`: must be between 4 and 16"
+        );
+        match self.nixie.mode {
+            NixieMode::Price => {}
+            NixieMode::Alternate => anyhow::ensure!(
+                !(self.nixie.price_duration + self.nixie.time_duration).is_zero(),
+                "`nixie.price_duration`: can't be 0 along with `nixie.time_duration`"
+            ),
+            NixieMode::Clock => anyhow::ensure!(
+                self.nixie.price_duration < Duration::from_secs(60),
+                "`nixie.price_duration`: must be under a minute in the clock mode"
+            ),
+        }
+        let cycle = &self.nixie.cathode_cycle;
+        anyhow::ensure!(
+            cycle.every.is_none_or(|every| every > cycle.duration),
+            "`nixie.cathode_cycle.every`: must be longer than `nixie.cathode_cycle.duration`"
+        );
+        let periods = &self.brightness.schedule;
+        for (i, period) in periods.iter().enumerate() {
+            anyhow::ensure!(
+                !periods[..i].iter().any(|p| p.from == period.from),
+                "`brightness.schedule[{i}].from`: {} is used twice",
+                period.from.format("%H:%M:%S")
+            );
+        }
+        let terminals = self.run.outputs.iter();
+        anyhow::ensure!(
+            terminals
+                .filter(|o| matches!(o, Output::Terminal(_)))
+                .count()
+                <= 1,
+            "`run.outputs`: at most one terminal output can be given"
+        );
+        Ok(())
+    }
+}
+
+pub(crate) fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    humantime::parse_duration(&s)
+        .map_err(|e| D::Error::custom(format!("invalid duration {s:?}: {e}")))
+}
+
+pub(crate) fn deserialize_optional_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    deserialize_duration(deserializer).map(Some)
+}
+
+/// "HH:MM" or "HH:MM:SS"
+pub fn parse_time_of_day(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M:%S"))
+        .map_err(|_| format!("invalid time of day {s:?}, expected HH:MM"))
+}
+
+pub(crate) fn deserialize_time_of_day<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<NaiveTime, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_time_of_day(&s).map_err(D::Error::custom)
+}
+
+pub(crate) fn deserialize_optional_time_of_day<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<NaiveTime>, D::Error> {
+    deserialize_time_of_day(deserializer).map(Some)
+}
+
+#[test]
+fn test_example_config() {
+    use super::brightness::Level;
+    use super::screen::face::{Align, Icon, Region, VAlign, Widget};
+    use rust_decimal_macros::dec;
+
+    let config = Config::parse(include_str!("../cyberpunk.example.toml")).unwrap();
+    config.validate().unwrap();
+    assert_eq!(config.markets.len(), 2);
+    assert_eq!(config.markets[1].symbol, "ETHUSDT");
+    assert_eq!(config.source.reconnect_delay, Duration::from_secs(30));
+    assert_eq!(config.alerts[0].above, Some(dec!(100000)));
+    assert_eq!(config.awtrix.min_interval, Some(Duration::from_millis(100)));
+    assert_eq!(config.awtrix.protocol, Protocol::V3);
+    assert_eq!(config.colors.price.to_string(), "#ffc800");
+    assert_eq!(config.colors.up.to_string(), "#00cc00");
+    assert_eq!(config.layout.face.regions.len(), 4);
+    assert_eq!(
+        config.layout.face.regions[2],
+        Region::new(Widget::Icon)
+            .with_align(Align::Left, VAlign::Bottom)
+            .with_margin(0, 0, 0, 1)
+            .with_z(1)
+            .with_icon(Icon::Trend)
+    );
+    assert_eq!(config.run.outputs.len(), 2);
+    assert_eq!(config.nixie.cathode_cycle.duration, Duration::from_secs(5));
+    assert_eq!(config.nixie.protocol().digits(), 6);
+    assert_eq!(config.nixie.format.decimals, Some(1));
+    assert_eq!(config.nixie.heartbeat, Heartbeat::Direction);
+    assert_eq!(config.brightness.schedule[2].level, Level::Off);
+    assert_eq!(
+        config.metrics.address,
+        Some("0.0.0.0:9100".parse().unwrap())
+    );
+}
+
+#[test]
+fn test_config_errors() {
+    let error = |text: &str| {
+        let config = Config::parse(text);
+        match config {
+            Ok(config) => config.validate().unwrap_err().to_string(),
+            Err(e) => e.to_string(),
+        }
+    };
+    Config::default().validate().unwrap();
+    assert_eq!(
+        error("[awtrix]\nhost = \"clock\"\nport = 70000"),
+        "`awtrix.port` (line 3): invalid value: integer `70000`, expected u16"
+    );
+    assert!(error("[awtrix]\nprotocl = \"v3\"")
+        .starts_with("`awtrix.protocl` (line 2): unknown field `protocl`, expected one of"));
+    assert!(error("[awtrix\nport = 1").starts_with("TOML parse error at line 1"));
+    assert_eq!(
+        error("[colors]\nup = \"green\""),
+        "`colors.up` (line 2): invalid color \"green\", expected #rrggbb"
+    );
+    assert_eq!(
+        error("[run]\noutputs = [\"terminal\", \"lcd\"]"),
+        "`run.outputs[1]` (line 2): unknown output \"lcd\", expected terminal, awtrix, awtrix3, web or nixie"
+    );
+    assert_eq!(
+        error("[[alerts]]\nmarket = \"ETH\"\nabove = 5000"),
+        "`alerts[0].market`: unknown market \"ETH\""
+    );
+    assert_eq!(
+        error("[nixie]\nbrightness = 9"),
+        "`nixie.brightness`: must be between 0 and 8"
+    );
+    assert!(error("[[layout.face]]\nwidget = \"weather\"")
+        .starts_with("`layout.face[0].widget` (line 2): unknown variant `weather`"));
+    assert_eq!(
+        error("[layout]\nheight = 0"),
+        "`layout`: height must be between 1 and 256"
+    );
+    assert_eq!(
+        error("[nixie]\ntubes = 3"),
+        "`nixie.tubes`: must be between 4 and 16"
+    );
+    assert_eq!(
+        error("[brightness]\nschedule = [{ from = \"08:00\", level = \"dim\" }]"),
+        "`brightness.schedule[0].level` (line 2): invalid level \"dim\", expected 0 to 255 or \"off\""
+    );
+    assert_eq!(
+        error("[brightness]\nschedule = [{ from = \"25:00\", level = 1 }]"),
+        "`brightness.schedule[0].from` (line 2): invalid time of day \"25:00\", expected HH:MM"
+    );
+    assert_eq!(
+        error("[nixie]\nmode = \"clock\"\nprice_duration = \"1m\""),
+        "`nixie.price_duration`: must be under a minute in the clock mode"
+    );
+}
@@ -0,0 +1,360 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::watch;
+
+use super::{
+    feed::{FeedSettings, Frame},
+    matrix::Layout,
+    sink::{Dimmer, DisplaySink},
+};
+
+/// A REST API to control the running display, e.g. from home automation scripts:
+///
+/// - `GET /state`: connection, market shown, paused or not, the brightness set, and whether the
+///   displays are blanked on schedule
+/// - `GET /price`: the latest price of the market shown
+/// - `GET /frame`, `GET /frame.png?scale=<n>`: the last frame rendered
+/// - `PUT /brightness` with `{"brightness": <0-255>}`: dim every output
+/// - `POST /message` with `{"text": "..."}`: show a message as alerts are
+/// - `POST /pause`, `POST /resume`: freeze the outputs on their last frame, or not
+/// - `PUT /layout` with a `[layout]` section of the config file as JSON, 422 if it can't be drawn
+/// - `PUT /market` with `{"name": "ETH"}`: show another of the markets followed
+///
+/// It's an output of its own, to know the last frame rendered.
+pub struct ControlApi {
+    addr: SocketAddr,
+    frames: watch::Sender<Option<Frame>>,
+    server: tokio::task::JoinHandle<std::io::Result<()>>,
+}
+
+impl ControlApi {
+    /// Start serving on `addr`, failing right away if it can't be bound.
+    pub async fn bind(
+        addr: SocketAddr,
+        feed: FeedSettings,
+        dimmer: Dimmer,
+    ) -> anyhow::Result<Self> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        log::info!("Serving control API on http://{addr}");
+        let frames = watch::Sender::new(None);
+        let app = router(Control {
+            feed,
+            dimmer,
+            frames: frames.subscribe(),
+        });
+        let server = tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok(ControlApi {
+            addr,
+            frames,
+            server,
+        })
+    }
+}
+
+impl DisplaySink for ControlApi {
+    fn name(&self) -> String {
+        format!("control API on http://{}", self.addr)
+    }
+
+    async fn render(&mut self, frame: &Frame) -> anyhow::Result<()> {
+        anyhow::ensure!(!self.server.is_finished(), "the server stopped");
+        self.frames.send_replace(Some(frame.clone()));
+        Ok(())
+    }
+
+    async fn clear(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.server.abort();
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct Control {
+    feed: FeedSettings,
+    dimmer: Dimmer,
+    frames: watch::Receiver<Option<Frame>>,
+}
+
+impl Control {
+    fn frame(&self) -> Result<Frame, (StatusCode, String)> {
+        self.frames
+            .borrow()
+            .clone()
+            .ok_or((StatusCode::NOT_FOUND, "nothing rendered yet".to_string()))
+    }
+}
+
+type Reply = Result<Json<Value>, (StatusCode, String)>;
+
+fn router(control: Control) -> Router {
+    Router::new()
+        .route("/state", get(state))
+        .route("/price", get(price))
+        .route("/frame", get(frame))
+        .route("/frame.png", get(frame_png))
+        .route("/brightness", put(brightness))
+        .route("/message", post(message))
+        .route("/pause", post(|s| set_paused(s, true)))
+        .route("/resume", post(|s| set_paused(s, false)))
+        .route("/layout", put(layout))
+        .route("/market", put(market))
+        .with_state(control)
+}
+
+async fn state(State(control): State<Control>) -> Reply {
+    let markets = control.feed.markets();
+    let connection = control
+        .frames
+        .borrow()
+        .as_ref()
+        .map(|f| f.connection.to_string());
+    Ok(Json(json!({
+        "connection": connection,
+        "market": markets[0].name,
+        "markets": markets.iter().map(|m| &m.name).collect::<Vec<_>>(),
+        "paused": control.feed.is_paused(),
+        "brightness": control.dimmer.level(),
+        "blanked": control.dimmer.is_blanked(),
+    })))
+}
+
+async fn price(State(control): State<Control>) -> Reply {
+    let price = control
+        .frame()?
+        .price
+        .ok_or((StatusCode::NOT_FOUND, "no price yet".to_string()))?;
+    Ok(Json(json!({
+        "name": price.name,
+        "price": price.price,
+        "change_24h": price.change_24h,
+    })))
+}
+
+/// Every pixel as `#rrggbb`, or `null` when unlit, row by row
+async fn frame(State(control): State<Control>) -> Reply {
+    let screen = control.frame()?.screen;
+    let pixels: Vec<Vec<Option<String>>> = screen
+        .pixels
+        .iter()
+        .map(|row| row.iter().map(|p| p.map(|p| p.to_string())).collect())
+        .collect();
+    Ok(Json(json!({
+        "width": pixels[0].len(),
+        "height": pixels.len(),
+        "pixels": pixels,
+    })))
+}
+
+#[derive(Deserialize)]
+struct PngQuery {
+    #[serde(default = "default_scale")]
+    scale: usize,
+}
+
+fn default_scale() -> usize {
+    10
+}
+
+async fn frame_png(
+    State(control): State<Control>,
+    Query(query): Query<PngQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    if !(1..=100).contains(&query.scale) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "scale should be between 1 and 100".to_string(),
+        ));
+    }
+    let png = control.frame()?.screen.to_png(query.scale);
+    Ok(([(header::CONTENT_TYPE, "image/png")], png).into_response())
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Brightness {
+    brightness: u8,
+}
+
+async fn brightness(State(control): State<Control>, Json(body): Json<Brightness>) -> Reply {
+    log::info!("Setting the brightness to {}", body.brightness);
+    control.dimmer.set(body.brightness);
+    state(State(control)).await
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Message {
+    text: String,
+}
+
+async fn message(State(control): State<Control>, Json(body): Json<Message>) -> Reply {
+    log::info!("Showing message {:?}", body.text);
+    control.feed.show_message(body.text);
+    Ok(Json(json!({})))
+}
+
+async fn set_paused(State(control): State<Control>, paused: bool) -> Reply {
+    log::info!(
+        "{} the outputs",
+        if paused { "Pausing" } else { "Resuming" }
+    );
+    control.feed.set_paused(paused);
+    state(State(control)).await
+}
+
+async fn layout(State(control): State<Control>, Json(layout): Json<Layout>) -> Reply {
+    if let Err(e) = layout.validate() {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, e));
+    }
+    log::info!("Switching to {layout:?}");
+    control.feed.set_layout(layout);
+    state(State(control)).await
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MarketName {
+    name: String,
+}
+
+async fn market(State(control): State<Control>, Json(body): Json<MarketName>) -> Reply {
+    let mut markets = control.feed.markets();
+    let Some(i) = markets.iter().position(|m| m.name == body.name) else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("{:?} isn't followed", body.name),
+        ));
+    };
+    if i != 0 {
+        log::info!("Showing {}", body.name);
+        let market = markets.remove(i);
+        markets.insert(0, market);
+        control.feed.set_markets(markets);
+    }
+    state(State(control)).await
+}
+
+#[tokio::test]
+async fn test_control() {
+    use super::{
+        screen::Screen,
+        ws_coin::{ConnectionState, Market, Price},
+    };
+    use rust_decimal_macros::dec;
+
+    let market = |name: &str| Market {
+        symbol: format!("{name}USDT"),
+        name: name.to_string(),
+    };
+    let feed = FeedSettings::detached(vec![market("BTC"), market("ETH")]);
+    let dimmer = Dimmer::default();
+    let mut api = ControlApi::bind("127.0.0.1:0".parse().unwrap(), feed.clone(), dimmer.clone())
+        .await
+        .unwrap();
+    let url = format!("http://{}", api.addr);
+    let client = reqwest::Client::new();
+    let send = |request: reqwest::RequestBuilder, body: &str| {
+        request
+            .header("content-type", "application/json")
+            .body(body.to_string())
+            .send()
+    };
+    let json = |response: reqwest::Response| async move {
+        assert!(response.status().is_success(), "{}", response.status());
+        serde_json::from_str::<Value>(&response.text().await.unwrap()).unwrap()
+    };
+
+    let response = client.get(format!("{url}/price")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    api.render(&Frame {
+        screen: Screen::default(),
+        price: Some(Price {
+            symbol: "BTCUSDT".to_string(),
+            name: "BTC".to_string(),
+            price: dec!(100000.5),
+            change_24h: Some(dec!(-1.25)),
+        }),
+        new_price: true,
+        connection: ConnectionState::Connected,
+    })
+    .await
+    .unwrap();
+    let price = json(client.get(format!("{url}/price")).send().await.unwrap()).await;
+    assert_eq!(
+        price,
+        json!({"name": "BTC", "price": 100000.5, "change_24h": -1.25})
+    );
+    let frame = json(client.get(format!("{url}/frame")).send().await.unwrap()).await;
+    assert_eq!(frame["width"], 32);
+    let png = client
+        .get(format!("{url}/frame.png?scale=2"))
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert_eq!(png[..4], *b"\x89PNG");
+
+    let state = json(
+        send(
+            client.put(format!("{url}/brightness")),
+            r#"{"brightness": 10}"#,
+        )
+        .await
+        .unwrap(),
+    )
+    .await;
+    assert_eq!(state["brightness"], 10);
+    assert_eq!(dimmer.level(), Some(10));
+
+    let state = json(client.post(format!("{url}/pause")).send().await.unwrap()).await;
+    assert_eq!(state["paused"], true);
+    assert!(feed.is_paused());
+
+    let state = json(
+        send(client.put(format!("{url}/market")), r#"{"name": "ETH"}"#)
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(state["market"], "ETH");
+    assert_eq!(state["markets"], json!(["ETH", "BTC"]));
+    let response = send(client.put(format!("{url}/market")), r#"{"name": "DOGE"}"#)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send(
+        client.put(format!("{url}/layout")),
+        r#"{"time_format": "%Q"}"#,
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    // no pixel to draw on
+    let response = send(client.put(format!("{url}/layout")), r#"{"height": 0}"#)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "height must be between 1 and 256"
+    );
+    api.shutdown().await.unwrap();
+}
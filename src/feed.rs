@@ -0,0 +1,256 @@
+use tokio::sync::{broadcast, mpsc, watch};
+
+use super::{
+    alert::{Alert, Alerts},
+    matrix::{BtcTimeMatrix, Layout},
+    screen::{palette::Palette, Screen},
+    ws_coin::{ConnectionState, Market, Price},
+};
+
+/// How many frames a slow output may fall behind before it starts skipping them
+const FRAME_CAPACITY: usize = 16;
+
+/// What every output is sent: the rendered matrix, and the data behind it for outputs that draw
+/// their own way.
+#[derive(Clone)]
+pub struct Frame {
+    pub screen: Screen,
+    /// The latest price, once one was received
+    pub price: Option<Price>,
+    /// Whether `price` came in with this frame, rather than the matrix being redrawn for the clock
+    /// or a transition
+    pub new_price: bool,
+    pub connection: ConnectionState,
+}
+
+/// One price feed and matrix, shared by every output of the process.
+///
+/// The matrix runs in a background task, broadcasting a frame for each screen it renders, and the
+/// message of every alert a price fires; outputs subscribe to the frames, the alerts, or to every
+/// single price.
+pub struct Feed {
+    frames: broadcast::Sender<Frame>,
+    alerts: broadcast::Sender<String>,
+    prices: broadcast::Receiver<Price>, // kept to hand out new subscriptions
+    state: watch::Receiver<ConnectionState>,
+    settings: FeedSettings,
+    task: tokio::task::JoinHandle<()>,
+}
+
+enum Update {
+    Markets(Vec<Market>),
+    Layout(Layout),
+    Palette(Palette),
+    Alerts(Vec<Alert>),
+    Message(String),
+}
+
+/// Changes the settings of a running [`Feed`], keeping its price history.
+#[derive(Clone)]
+pub struct FeedSettings {
+    updates: mpsc::UnboundedSender<Update>,
+    markets: watch::Sender<Vec<Market>>,
+    paused: watch::Sender<bool>,
+}
+
+impl FeedSettings {
+    /// See [`BtcTimeMatrix::set_markets`]
+    pub fn set_markets(&self, markets: Vec<Market>) {
+        self.markets.send_replace(markets.clone());
+        let _ = self.updates.send(Update::Markets(markets)); // the feed may be gone
+    }
+
+    /// The markets followed, the first one being shown
+    pub fn markets(&self) -> Vec<Market> {
+        self.markets.borrow().clone()
+    }
+
+    /// The markets followed, as they're changed
+    pub fn subscribe_markets(&self) -> watch::Receiver<Vec<Market>> {
+        self.markets.subscribe()
+    }
+
+    pub fn set_layout(&self, layout: Layout) {
+        let _ = self.updates.send(Update::Layout(layout));
+    }
+
+    pub fn set_palette(&self, palette: Palette) {
+        let _ = self.updates.send(Update::Palette(palette));
+    }
+
+    pub fn set_alerts(&self, alerts: Vec<Alert>) {
+        let _ = self.updates.send(Update::Alerts(alerts));
+    }
+
+    /// Show `message` on the outputs that can, as an alert is
+    pub fn show_message(&self, message: String) {
+        let _ = self.updates.send(Update::Message(message));
+    }
+
+    /// Stop sending frames, the outputs keeping the last one, while prices are still followed
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.send_replace(paused);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Settings not connected to any feed, for tests
+    #[cfg(test)]
+    pub(crate) fn detached(markets: Vec<Market>) -> Self {
+        FeedSettings {
+            updates: mpsc::unbounded_channel().0,
+            markets: watch::Sender::new(markets),
+            paused: watch::Sender::new(false),
+        }
+    }
+}
+
+impl Feed {
+    pub fn new(mut matrix: BtcTimeMatrix, alerts: Vec<Alert>) -> Self {
+        let frames = broadcast::Sender::new(FRAME_CAPACITY);
+        let alert_tx = broadcast::Sender::new(FRAME_CAPACITY);
+        let prices = matrix.subscribe_prices();
+        let state = matrix.connection_state();
+
+        let tx = frames.clone();
+        let fired = alert_tx.clone();
+        let connection = state.clone();
+        let mut latest = matrix.latest_price();
+        let mut checked = matrix.subscribe_prices();
+        let mut alerts = Alerts::new(alerts);
+        let (updates, mut updated) = mpsc::unbounded_channel();
+        let settings = FeedSettings {
+            updates,
+            markets: watch::Sender::new(matrix.markets().to_vec()),
+            paused: watch::Sender::new(false),
+        };
+        let paused = settings.paused.subscribe();
+        let task = tokio::spawn(async move {
+            loop {
+                let screen = tokio::select! {
+                    screen = matrix.gen_screen() => screen,
+                    Some(update) = updated.recv() => {
+                        match update {
+                            Update::Markets(markets) => matrix.set_markets(markets),
+                            Update::Layout(layout) => matrix.set_layout(layout),
+                            Update::Palette(palette) => matrix.set_palette(palette),
+                            Update::Alerts(new_alerts) => alerts.set_alerts(new_alerts),
+                            Update::Message(message) => {
+                                let _ = fired.send(message);
+                            }
+                        }
+                        continue;
+                    }
+                };
+                // every price comes with a new screen, so checking them here is timely enough
+                loop {
+                    let price = match checked.try_recv() {
+                        Ok(price) => price,
+                        Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                        Err(_) => break,
+                    };
+                    for alert in alerts.check(&price) {
+                        log::warn!("Alert: {alert}");
+                        let _ = fired.send(alert);
+                    }
+                }
+                let new_price = latest.has_changed().unwrap_or(false);
+                let frame = Frame {
+                    screen,
+                    price: latest.borrow_and_update().clone(),
+                    new_price,
+                    connection: connection.borrow().clone(),
+                };
+                if !*paused.borrow() {
+                    let _ = tx.send(frame); // nobody may be listening yet
+                }
+            }
+        });
+        Feed {
+            frames,
+            alerts: alert_tx,
+            prices,
+            state,
+            settings,
+            task,
+        }
+    }
+
+    pub fn settings(&self) -> FeedSettings {
+        self.settings.clone()
+    }
+
+    pub fn frames(&self) -> broadcast::Receiver<Frame> {
+        self.frames.subscribe()
+    }
+
+    pub fn alerts(&self) -> broadcast::Receiver<String> {
+        self.alerts.subscribe()
+    }
+
+    pub fn prices(&self) -> broadcast::Receiver<Price> {
+        self.prices.resubscribe()
+    }
+
+    pub fn connection_state(&self) -> watch::Receiver<ConnectionState> {
+        self.state.clone()
+    }
+}
+
+impl Drop for Feed {
+    fn drop(&mut self) {
+        // also closes the price feed, which the matrix owns
+        self.task.abort();
+    }
+}
+
+/// Wait for the next item, then skip to the latest one if more are queued, so that a slow output
+/// always shows the freshest data. `None` once the feed is closed.
+pub async fn recv_latest<T: Clone>(rx: &mut broadcast::Receiver<T>) -> Option<T> {
+    recv_latest_counted(rx).await.map(|(item, _)| item)
+}
+
+/// [`recv_latest`], along with how many items were skipped to get to it
+pub async fn recv_latest_counted<T: Clone>(rx: &mut broadcast::Receiver<T>) -> Option<(T, u64)> {
+    let mut skipped = 0;
+    let mut item = loop {
+        match rx.recv().await {
+            Ok(item) => break item,
+            Err(broadcast::error::RecvError::Lagged(n)) => skipped += n,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    };
+    loop {
+        match rx.try_recv() {
+            Ok(next) => {
+                item = next;
+                skipped += 1;
+            }
+            Err(broadcast::error::TryRecvError::Lagged(n)) => skipped += n,
+            Err(_) => return Some((item, skipped)),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_recv_latest() {
+    let (tx, mut rx) = broadcast::channel(2);
+    for i in 0..5 {
+        tx.send(i).unwrap();
+    }
+    // 0..=2 were overwritten, 3 is skipped as 4 is already there
+    assert_eq!(recv_latest(&mut rx).await, Some(4));
+
+    tx.send(5).unwrap();
+    assert_eq!(recv_latest(&mut rx).await, Some(5));
+
+    for i in 6..9 {
+        tx.send(i).unwrap();
+    }
+    assert_eq!(recv_latest_counted(&mut rx).await, Some((8, 2)));
+
+    drop(tx);
+    assert_eq!(recv_latest(&mut rx).await, None);
+}
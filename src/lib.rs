@@ -1,9 +1,20 @@
+pub mod alert;
 pub mod awtrix;
+pub mod brightness;
+pub mod config;
+pub mod control;
+pub mod feed;
 pub mod matrix;
-#[cfg(feature = "nixie")]
+pub mod metrics;
+pub mod mqtt;
 pub mod nixie;
+pub mod output;
 pub mod price_queue;
+pub mod reload;
 pub mod screen;
+pub mod sink;
+pub mod terminal;
+pub mod web;
 pub mod ws_coin;
 
 #[test]
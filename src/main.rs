@@ -1,164 +1,668 @@
 use clap::Parser;
 use cyberpunk_display::awtrix;
+use cyberpunk_display::brightness::BrightnessSchedule;
+use cyberpunk_display::config::{Config, Secret};
+use cyberpunk_display::control::ControlApi;
+use cyberpunk_display::feed::Feed;
 use cyberpunk_display::matrix::BtcTimeMatrix;
+use cyberpunk_display::metrics;
+use cyberpunk_display::mqtt::{MqttClient, MqttSink, MqttTopics};
 #[cfg(feature = "nixie")]
 use cyberpunk_display::nixie;
-#[cfg(feature = "nixie")]
-use cyberpunk_display::ws_coin::WsCoin;
-use futures::StreamExt as _;
-use simplelog::{ColorChoice, CombinedLogger, LevelFilter, TermLogger, TerminalMode, WriteLogger};
+use cyberpunk_display::output::Output;
+use cyberpunk_display::reload::Reloader;
+use cyberpunk_display::screen::export::GifFile;
+use cyberpunk_display::screen::term_style::TermStyle;
+use cyberpunk_display::screen::theme::ThemeName;
+use cyberpunk_display::screen::transition::Transition;
+use cyberpunk_display::sink::Drivers;
+use cyberpunk_display::terminal::{LogPane, Terminal};
+use cyberpunk_display::web::WebPreview;
+use simplelog::{
+    ColorChoice, CombinedLogger, LevelFilter, SharedLogger, TermLogger, TerminalMode, WriteLogger,
+};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::process::ExitCode;
 use std::time::Duration;
 use std::{
     fs::{create_dir_all, File},
     path::PathBuf,
 };
+use tokio::sync::watch;
 
-async fn drain_stream_or_wait<T>(
-    stream: &mut (impl futures::Stream<Item = T> + Unpin),
-) -> Option<T> {
-    use futures::{FutureExt, StreamExt};
-
-    let mut item: Option<T> = None;
-    loop {
-        let Some(next) = stream.next().now_or_never() else {
-            // stream is drained
-            if item.is_none() {
-                // still empty, wait for next item
-                return stream.next().await;
-            }
-            return item;
-        };
-        let Some(next) = next else {
-            // stream is closed
-            return item;
-        };
-        item = Some(next);
-    }
-}
-
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 struct Opts {
+    /// Read settings from this TOML file, see cyberpunk.example.toml; command line flags take
+    /// precedence over it
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+    /// Serve a REST API controlling the display on this address, e.g. 127.0.0.1:8081, see
+    /// README.md
+    #[clap(long, global = true)]
+    control: Option<SocketAddr>,
+    /// Serve Prometheus metrics on this address, e.g. 0.0.0.0:9100, at /metrics
+    #[clap(long, global = true)]
+    metrics: Option<SocketAddr>,
+    /// Colors of the matrix, in place of those of the config file
+    #[clap(long, global = true, value_enum)]
+    theme: Option<ThemeName>,
     #[clap(subcommand)]
     subcmd: SubCommand,
 }
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 enum SubCommand {
-    Matrix,
+    Matrix(Matrix),
     Awtrix(Awtrix),
+    /// Live preview of the matrix in a web browser
+    Web(Web),
+    /// Publish the matrix to an Awtrix 3 clock and the price to Home Assistant over MQTT
+    Mqtt(Mqtt),
     #[cfg(feature = "nixie")]
     Nixie(Nixie),
+    /// Drive several outputs at once from a single price feed
+    Run(Run),
 }
 
-#[derive(Parser, Debug)]
+/// How long each intermediate frame of a price transition stays on screen
+const TRANSITION_FRAME_INTERVAL: Duration = Duration::from_millis(60);
+
+#[derive(Parser, Debug, Clone)]
+struct Matrix {
+    /// Animation between two consecutive prices [default: none]
+    #[clap(long, value_enum)]
+    transition: Option<Transition>,
+    /// Terminal render mode as <glyphs>[:<colors>], glyphs being auto, block, half-block, braille
+    /// or ascii and colors truecolor, 256 or 16; what's auto or left out is detected from
+    /// COLORTERM and TERM
+    /// [default: auto]
+    #[clap(long)]
+    term_style: Option<TermStyle>,
+    /// Record the rendered frames into an animated GIF
+    #[clap(long, requires = "duration")]
+    record_gif: Option<PathBuf>,
+    /// Size of one LED in the recording (in image pixels)
+    #[clap(long, default_value = "10", value_parser = clap::value_parser!(u32).range(1..))]
+    record_scale: u32,
+    /// Exit after running for this long, e.g. "60s" or "5m"
+    #[clap(long, value_parser = humantime::parse_duration)]
+    duration: Option<Duration>,
+}
+
+#[derive(Parser, Debug, Clone)]
 struct Awtrix {
-    #[clap(long, default_value = "localhost")]
-    host: String,
-    #[clap(long, default_value = "7000")]
-    port: u16,
+    /// [default: localhost]
+    #[clap(long)]
+    host: Option<String>,
+    /// [default: 7000]
+    #[clap(long)]
+    port: Option<u16>,
     /// Minimum interval between updates (in milliseconds)
     #[clap(long)]
-    min_interval: Option<u128>,
+    min_interval: Option<u64>,
     /// Print matrix to terminal before sending to awtrix
     #[clap(long)]
     print: bool,
+    /// Terminal render mode used by --print, see `matrix --help` [default: auto]
+    #[clap(long)]
+    term_style: Option<TermStyle>,
+    /// API of the device: v2 for an Awtrix 2 host, v3 for Awtrix 3 firmware (usually on port 80)
+    /// [default: v2]
+    #[clap(long, value_enum)]
+    protocol: Option<awtrix::Protocol>,
+    /// Name of the custom app showing the matrix, with --protocol v3 [default: cyberpunk]
+    #[clap(long)]
+    app_name: Option<String>,
+    /// Animation between two consecutive prices [default: none]
+    #[clap(long, value_enum)]
+    transition: Option<Transition>,
+}
+
+#[derive(Parser, Debug, Clone)]
+struct Web {
+    /// [default: 127.0.0.1]
+    #[clap(long)]
+    host: Option<std::net::IpAddr>,
+    /// [default: 8080]
+    #[clap(long)]
+    port: Option<u16>,
+    /// Animation between two consecutive prices [default: none]
+    #[clap(long, value_enum)]
+    transition: Option<Transition>,
+}
+
+#[derive(Parser, Debug, Clone)]
+struct Run {
+    /// Where to show the feed, repeatable: terminal[=<term-style>], awtrix=<host>[:<port>],
+    /// awtrix3=<host>[:<port>], web[=<ip>:<port>] or nixie[=<serial-port>]; replaces the
+    /// outputs of the config file
+    #[clap(long = "output")]
+    outputs: Vec<Output>,
+    /// Animation between two consecutive prices [default: none]
+    #[clap(long, value_enum)]
+    transition: Option<Transition>,
+}
+
+#[derive(Parser, Debug, Clone)]
+struct Mqtt {
+    /// MQTT broker [default: localhost]
+    #[clap(long)]
+    host: Option<String>,
+    /// [default: 1883]
+    #[clap(long)]
+    port: Option<u16>,
+    #[clap(long, requires = "password")]
+    username: Option<String>,
+    #[clap(long, requires = "username")]
+    password: Option<Secret>,
+    /// [default: cyberpunk_display]
+    #[clap(long)]
+    client_id: Option<String>,
+    /// MQTT prefix of the Awtrix 3 clock [default: awtrix]
+    #[clap(long)]
+    awtrix_prefix: Option<String>,
+    /// Name of the custom app showing the matrix on the clock [default: cyberpunk]
+    #[clap(long)]
+    app_name: Option<String>,
+    /// Prefix of the price topics, e.g. <topic-prefix>/btc/price and <topic-prefix>/btc/change_24h
+    /// [default: cyberpunk_display]
+    #[clap(long)]
+    topic_prefix: Option<String>,
+    /// Home Assistant discovery prefix [default: homeassistant]
+    #[clap(long)]
+    discovery_prefix: Option<String>,
+    /// Don't announce the price sensors to Home Assistant
+    #[clap(long)]
+    no_discovery: bool,
+    /// Animation between two consecutive prices [default: none]
+    #[clap(long, value_enum)]
+    transition: Option<Transition>,
 }
 
 #[cfg(feature = "nixie")]
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 struct Nixie {
-    #[clap(short, long, default_value = "/dev/ttyUSB0")]
-    serial_port: String,
-    #[clap(short, long, default_value = "8")]
-    brightness: u8,
+    /// [default: /dev/ttyUSB0]
+    #[clap(short, long)]
+    serial_port: Option<String>,
+    /// From 0 to 8 [default: 8]
+    #[clap(short, long)]
+    brightness: Option<u8>,
+    /// What the board understands: the tube boards, or a Noritake CU-series VFD [default: timd]
+    #[clap(long, value_enum)]
+    protocol: Option<nixie::ProtocolKind>,
+    /// How many tubes the board has, or digits the VFD shows [default: 6]
+    #[clap(long)]
+    tubes: Option<usize>,
+    /// Where the decimal points sit on the tubes [default: left]
+    #[clap(long, value_enum)]
+    dots: Option<nixie::Dots>,
+    /// Decimals of the price, fewer if it wouldn't fit otherwise [default: as many as fit]
+    #[clap(long)]
+    decimals: Option<u32>,
+    /// Show the price in thousands, a lit decimal point after it standing for "k"
+    #[clap(long)]
+    thousands: bool,
+    /// Where a price shorter than the tubes sits [default: left]
+    #[clap(long, value_enum)]
+    align: Option<nixie::Align>,
+    /// Leave the zeros padding a right aligned price unlit
+    #[clap(long)]
+    blank_leading_zeros: bool,
+    /// What the first decimal point tells of the prices [default: blink]
+    #[clap(long, value_enum)]
+    heartbeat: Option<nixie::Heartbeat>,
+    /// Light the LEDs under the tubes in the up, down or flat color, by where the price goes
+    #[clap(long)]
+    underlight: bool,
+    /// Show the price, the time, or alternate between them [default: price]
+    #[clap(long, value_enum)]
+    mode: Option<nixie::Mode>,
+    /// How long the price is shown in each cycle of the alternate mode, or at the start of each
+    /// minute in the clock mode [default: 20s]
+    #[clap(long, value_parser = humantime::parse_duration)]
+    price_duration: Option<Duration>,
+    /// How long the time is shown in each cycle of the alternate mode [default: 5s]
+    #[clap(long, value_parser = humantime::parse_duration)]
+    time_duration: Option<Duration>,
+    /// Run every tube through all its digits at this interval, against cathode poisoning
+    #[clap(long, value_parser = humantime::parse_duration)]
+    cathode_cycle_every: Option<Duration>,
+    /// Run every tube through all its digits every day at this time, e.g. 03:00
+    #[clap(long, value_parser = cyberpunk_display::config::parse_time_of_day)]
+    cathode_cycle_at: Option<chrono::NaiveTime>,
 }
 
-#[tokio::main]
-async fn main() {
-    {
-        let log_config = simplelog::ConfigBuilder::new()
-            .set_time_format_rfc3339()
-            .build();
-        CombinedLogger::init(vec![
-            TermLogger::new(
-                LevelFilter::Info,
-                log_config.clone(),
-                TerminalMode::Mixed,
-                ColorChoice::Auto,
-            ),
-            WriteLogger::new(LevelFilter::Debug, log_config, {
-                let log_dir = PathBuf::from("tmp/logs");
-                create_dir_all(&log_dir).unwrap();
-                File::create(log_dir.join(format!(
-                        "{}-{}.log",
-                        chrono::Local::now().format("%Y%m%d%H%M%S"),
-                        &uuid::Uuid::new_v4()
-                            .as_hyphenated()
-                            .encode_lower(&mut uuid::Uuid::encode_buffer())[..8]
-                    )))
-                .unwrap()
-            }),
-        ])
-        .unwrap();
+/// Override `value` with the command line `flag`, if given
+fn set<T: Clone>(value: &mut T, flag: &Option<T>) {
+    if let Some(flag) = flag {
+        *value = flag.clone();
+    }
+}
+
+impl SubCommand {
+    /// Apply the command line flags on top of the config file
+    fn apply_to(&self, config: &mut Config) {
+        match self {
+            SubCommand::Matrix(m) => {
+                set(&mut config.layout.transition, &m.transition);
+                set(&mut config.terminal.style, &m.term_style);
+            }
+            SubCommand::Awtrix(a) => {
+                set(&mut config.awtrix.host, &a.host);
+                set(&mut config.awtrix.port, &a.port);
+                if let Some(ms) = a.min_interval {
+                    config.awtrix.min_interval = Some(Duration::from_millis(ms));
+                }
+                set(&mut config.terminal.style, &a.term_style);
+                set(&mut config.awtrix.protocol, &a.protocol);
+                set(&mut config.awtrix.app_name, &a.app_name);
+                set(&mut config.layout.transition, &a.transition);
+            }
+            SubCommand::Web(w) => {
+                set(&mut config.web.host, &w.host);
+                set(&mut config.web.port, &w.port);
+                set(&mut config.layout.transition, &w.transition);
+            }
+            SubCommand::Mqtt(q) => {
+                set(&mut config.mqtt.host, &q.host);
+                set(&mut config.mqtt.port, &q.port);
+                if q.username.is_some() {
+                    config.mqtt.username.clone_from(&q.username);
+                    config.mqtt.password.clone_from(&q.password);
+                }
+                set(&mut config.mqtt.client_id, &q.client_id);
+                set(&mut config.mqtt.awtrix_prefix, &q.awtrix_prefix);
+                set(&mut config.mqtt.app_name, &q.app_name);
+                set(&mut config.mqtt.topic_prefix, &q.topic_prefix);
+                set(&mut config.mqtt.discovery_prefix, &q.discovery_prefix);
+                if q.no_discovery {
+                    config.mqtt.discovery = false;
+                }
+                set(&mut config.layout.transition, &q.transition);
+            }
+            #[cfg(feature = "nixie")]
+            SubCommand::Nixie(n) => {
+                set(&mut config.nixie.serial_port, &n.serial_port);
+                set(&mut config.nixie.brightness, &n.brightness);
+                set(&mut config.nixie.protocol, &n.protocol);
+                set(&mut config.nixie.tubes, &n.tubes);
+                set(&mut config.nixie.dots, &n.dots);
+                let format = &mut config.nixie.format;
+                if n.decimals.is_some() {
+                    format.decimals = n.decimals;
+                }
+                format.thousands |= n.thousands;
+                set(&mut format.align, &n.align);
+                format.blank_leading_zeros |= n.blank_leading_zeros;
+                set(&mut config.nixie.heartbeat, &n.heartbeat);
+                config.nixie.underlight |= n.underlight;
+                set(&mut config.nixie.mode, &n.mode);
+                set(&mut config.nixie.price_duration, &n.price_duration);
+                set(&mut config.nixie.time_duration, &n.time_duration);
+                let cycle = &mut config.nixie.cathode_cycle;
+                if n.cathode_cycle_every.is_some() {
+                    cycle.every = n.cathode_cycle_every;
+                }
+                if n.cathode_cycle_at.is_some() {
+                    cycle.at = n.cathode_cycle_at;
+                }
+            }
+            SubCommand::Run(r) => {
+                if !r.outputs.is_empty() {
+                    config.run.outputs.clone_from(&r.outputs);
+                }
+                set(&mut config.layout.transition, &r.transition);
+            }
+        }
+    }
+}
+
+/// Set the tubes up as `[nixie]` says
+#[cfg(feature = "nixie")]
+fn configure_nixie(nixie: nixie::Nixie, config: &Config) -> nixie::Nixie {
+    let nixie = nixie
+        .with_protocol(config.nixie.protocol())
+        .with_number_format(config.nixie.format)
+        .with_schedule(config.nixie.schedule())
+        .with_cathode_cycle(config.nixie.cathode_cycle.clone())
+        .with_heartbeat(config.nixie.heartbeat);
+    match config.nixie.underlight {
+        true => nixie.with_underlight(config.colors),
+        false => nixie,
+    }
+}
+
+/// The file given with `--config` if any, overridden by the command line flags
+fn load_config(opts: &Opts) -> anyhow::Result<Config> {
+    let mut config = match &opts.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    if opts.control.is_some() {
+        config.control.address = opts.control;
+    }
+    if opts.metrics.is_some() {
+        config.metrics.address = opts.metrics;
+    }
+    if let Some(theme) = opts.theme {
+        config.colors = theme.theme().into();
+    }
+    opts.subcmd.apply_to(&mut config);
+    config.validate()?;
+    Ok(config)
+}
+
+/// The feed set up as in `config`, animating transitions at `frame_interval`, for the outputs to
+/// be added to; and the reloader applying the changes of the config file to it
+async fn start(opts: &Opts, config: &Config, frame_interval: Duration) -> (Drivers, Reloader) {
+    let matrix = BtcTimeMatrix::new(config.markets.clone(), config.source.clone())
+        .await
+        .with_layout(config.layout.clone())
+        .with_palette(config.colors)
+        .with_transition(config.layout.transition, frame_interval);
+    let feed = Feed::new(matrix, config.alerts.clone());
+    let opts = opts.clone();
+    let reloader = Reloader::new(
+        opts.config.clone(),
+        move || load_config(&opts),
+        config.clone(),
+        feed.settings(),
+    );
+    (Drivers::new(feed), reloader)
+}
+
+/// Log to a file, and to the terminal or to the `log_pane` if the terminal is taken over
+fn init_logging(log_pane: Option<LogPane>) {
+    let log_config = simplelog::ConfigBuilder::new()
+        .set_time_format_rfc3339()
+        .build();
+    let console: Box<dyn SharedLogger> = match log_pane {
+        Some(log_pane) => {
+            WriteLogger::new(LevelFilter::Info, log_config.clone(), log_pane.writer())
+        }
+        None => TermLogger::new(
+            LevelFilter::Info,
+            log_config.clone(),
+            TerminalMode::Mixed,
+            ColorChoice::Auto,
+        ),
+    };
+    CombinedLogger::init(vec![
+        console,
+        WriteLogger::new(LevelFilter::Debug, log_config, {
+            let log_dir = PathBuf::from("tmp/logs");
+            create_dir_all(&log_dir).unwrap();
+            File::create(log_dir.join(format!(
+                "{}-{}.log",
+                chrono::Local::now().format("%Y%m%d%H%M%S"),
+                &uuid::Uuid::new_v4()
+                    .as_hyphenated()
+                    .encode_lower(&mut uuid::Uuid::encode_buffer())[..8]
+            )))
+            .unwrap()
+        }),
+    ])
+    .unwrap();
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM as sent by `docker stop`
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        signal(SignalKind::terminate())
+            .expect("Failed to listen to SIGTERM")
+            .recv()
+            .await
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<Option<()>>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => log::info!("Received Ctrl-C, shutting down"),
+        _ = terminate => log::info!("Received SIGTERM, shutting down"),
+    }
+}
+
+/// Drive the outputs until `until` resolves or a shutdown signal, then release every device.
+/// The control API and the metrics are served alongside if their address is configured.
+async fn run(
+    mut drivers: Drivers,
+    reloader: Reloader,
+    config: &Config,
+    until: impl Future,
+) -> ExitCode {
+    if let Some(addr) = config.control.address {
+        match ControlApi::bind(addr, drivers.feed().settings(), drivers.dimmer()).await {
+            Ok(api) => drivers.add(api),
+            Err(e) => {
+                log::error!("Failed to serve the control API on {addr}: {e}");
+                drivers.run_until(async {}).await;
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let metrics = match config.metrics.address {
+        Some(addr) => match metrics::serve(addr).await {
+            Ok(server) => Some(server),
+            Err(e) => {
+                log::error!("Failed to serve the metrics on {addr}: {e}");
+                drivers.run_until(async {}).await;
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+    let schedule = reloader.brightness_schedule();
+    let reloading = tokio::spawn(reloader.run());
+    let scheduling = tokio::spawn(BrightnessSchedule::run(schedule, drivers.dimmer()));
+    let errors = drivers
+        .run_until(async {
+            tokio::select! {
+                _ = until => {},
+                _ = shutdown_signal() => {},
+            }
+        })
+        .await;
+    reloading.abort();
+    scheduling.abort();
+    if let Some(server) = metrics {
+        server.abort();
     }
+    for e in &errors {
+        log::error!("{e:#}");
+    }
+    if errors.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
 
+#[tokio::main]
+async fn main() -> ExitCode {
     let opts: Opts = Opts::parse();
+    let config = match load_config(&opts) {
+        Ok(config) => config,
+        Err(e) => {
+            // logging isn't set up yet, as where it goes depends on the config
+            eprintln!("Error: {e:#}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let terminal = match &opts.subcmd {
+        SubCommand::Matrix(_) => Some(config.terminal.style),
+        SubCommand::Awtrix(a) if a.print => Some(config.terminal.style),
+        SubCommand::Run(_) => config.run.outputs.iter().find_map(|o| match o {
+            Output::Terminal(style) => Some(*style),
+            _ => None,
+        }),
+        _ => None,
+    }
+    .map(Terminal::new)
+    .transpose();
+    let terminal = match terminal {
+        Ok(terminal) => terminal,
+        Err(e) => {
+            eprintln!("Error: Failed to set up the terminal: {e:#}");
+            return ExitCode::FAILURE;
+        }
+    };
+    init_logging(terminal.as_ref().and_then(Terminal::log_pane));
 
     log::info!("Starting application with {opts:?}");
+    log::debug!("Config: {config:?}");
 
-    match opts.subcmd {
-        SubCommand::Matrix => {
-            println!("\n\n\n\n\n\n\n\n");
-            let mut matrix = BtcTimeMatrix::default().await;
-            let matrix = matrix.subscribe();
-            tokio::pin!(matrix);
-            loop {
-                let Some(screen) = matrix.next().await else {
-                    continue;
-                };
-                println!("\x1b[8A{}", screen);
+    let code = match &opts.subcmd {
+        SubCommand::Matrix(m) => {
+            let size = (config.layout.width, config.layout.height);
+            let gif = match &m.record_gif {
+                Some(path) => match GifFile::create(path.clone(), m.record_scale as usize, size) {
+                    Ok(gif) => Some(gif),
+                    Err(e) => {
+                        log::error!("{e:#}");
+                        return ExitCode::FAILURE;
+                    }
+                },
+                None => None,
+            };
+            let (mut drivers, reloader) = start(&opts, &config, TRANSITION_FRAME_INTERVAL).await;
+            drivers.add(terminal.unwrap());
+            if let Some(gif) = gif {
+                drivers.add(gif);
             }
+            let until = async {
+                match m.duration {
+                    Some(duration) => tokio::time::sleep(duration).await,
+                    None => std::future::pending().await,
+                }
+            };
+            run(drivers, reloader, &config, until).await
         }
-        SubCommand::Awtrix(a) => {
-            let mut awtrix = awtrix::Awtrix::new(a.host, a.port, a.min_interval);
-            println!("\n\n\n\n\n\n\n\n");
-
-            let mut matrix = BtcTimeMatrix::default().await;
-            let matrix = matrix.subscribe();
-            tokio::pin!(matrix);
-            loop {
-                let screen = drain_stream_or_wait(&mut matrix).await.expect("closed");
-                if a.print {
-                    println!("\x1b[8A{}", screen);
+        SubCommand::Awtrix(_) => {
+            let a = &config.awtrix;
+            let awtrix = awtrix::Awtrix::new(
+                a.host.clone(),
+                a.port,
+                a.min_interval.map(|d| d.as_millis()),
+            )
+            .with_protocol(a.protocol, a.app_name.clone());
+
+            // never animate faster than awtrix is allowed to plot, or frames would be dropped
+            let frame_interval = TRANSITION_FRAME_INTERVAL.max(a.min_interval.unwrap_or_default());
+            let (mut drivers, reloader) = start(&opts, &config, frame_interval).await;
+            if let Some(mut terminal) = terminal {
+                terminal.watch_device("Awtrix", awtrix.online());
+                drivers.add(terminal);
+            }
+            drivers.add_dimmed(awtrix, watch::Sender::new(None)); // no brightness of its own
+            run(drivers, reloader, &config, std::future::pending::<()>()).await
+        }
+        SubCommand::Web(_) => {
+            match WebPreview::bind((config.web.host, config.web.port).into()).await {
+                Ok(web) => {
+                    let (mut drivers, reloader) =
+                        start(&opts, &config, TRANSITION_FRAME_INTERVAL).await;
+                    drivers.add(web);
+                    run(drivers, reloader, &config, std::future::pending::<()>()).await
+                }
+                Err(e) => {
+                    log::error!("Failed to serve the live preview: {e}");
+                    ExitCode::FAILURE
                 }
-                awtrix.plot(&screen.serialize()).await;
-                tokio::time::sleep(Duration::from_millis(50)).await;
             }
         }
+        SubCommand::Mqtt(_) => {
+            let q = &config.mqtt;
+            let client = MqttClient::connect(
+                &q.host,
+                q.port,
+                &q.client_id,
+                q.username.clone().zip(q.password.clone().map(|p| p.0)),
+            );
+            let sink = MqttSink::new(
+                client,
+                MqttTopics {
+                    awtrix_prefix: q.awtrix_prefix.clone(),
+                    app_name: q.app_name.clone(),
+                    state_prefix: q.topic_prefix.clone(),
+                    discovery_prefix: q.discovery.then(|| q.discovery_prefix.clone()),
+                },
+            );
+            let (mut drivers, reloader) = start(&opts, &config, TRANSITION_FRAME_INTERVAL).await;
+            drivers.add_dimmed(sink, watch::Sender::new(None)); // no brightness of its own
+            run(drivers, reloader, &config, std::future::pending::<()>()).await
+        }
         #[cfg(feature = "nixie")]
-        SubCommand::Nixie(n) => {
-            use cyberpunk_display::nixie::NixieMsg;
-
-            let mut nixie = nixie::Nixie::new(n.serial_port);
-            nixie.set_brightness(n.brightness);
-            let mut ws_coin = WsCoin::default().await;
-            let ws_coin = ws_coin.subscribe();
-            tokio::pin!(ws_coin);
-
-            let mut flip = false;
-            loop {
-                let price = drain_stream_or_wait(&mut ws_coin)
-                    .await
-                    .expect("WebSocket closed unexpectedly");
-
-                log::debug!("Received price: {price:?}");
-                let mut msg: NixieMsg = price.price.into();
-                flip = !flip;
-                if flip {
-                    msg.flip_first_decimal_point()
-                };
-                nixie.send(msg).await;
+        SubCommand::Nixie(_) => {
+            let nixie = match nixie::Nixie::new(config.nixie.serial_port.clone()) {
+                Ok(nixie) => configure_nixie(nixie, &config),
+                Err(e) => {
+                    log::error!("{e:#}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let (mut drivers, reloader) = start(&opts, &config, TRANSITION_FRAME_INTERVAL).await;
+            drivers.add_dimmed(nixie, reloader.nixie_brightness());
+            run(drivers, reloader, &config, std::future::pending::<()>()).await
+        }
+        SubCommand::Run(_) => {
+            if config.run.outputs.is_empty() {
+                log::error!("No output given, use --output or `run.outputs` in the config file");
+                return ExitCode::FAILURE;
+            }
+            let (mut drivers, reloader) = start(&opts, &config, TRANSITION_FRAME_INTERVAL).await;
+            let mut terminal = terminal;
+            let mut opened = true;
+            for output in config.run.outputs.clone() {
+                match output {
+                    Output::Terminal(_) => drivers.add(terminal.take().expect("a single terminal")),
+                    Output::Awtrix {
+                        host,
+                        port,
+                        protocol,
+                    } => drivers.add_dimmed(
+                        awtrix::Awtrix::new(
+                            host,
+                            port,
+                            config.awtrix.min_interval.map(|d| d.as_millis()),
+                        )
+                        .with_protocol(protocol, config.awtrix.app_name.clone()),
+                        watch::Sender::new(None), // no brightness of its own
+                    ),
+                    Output::Web(addr) => match WebPreview::bind(addr).await {
+                        Ok(web) => drivers.add(web),
+                        Err(e) => {
+                            log::error!("Failed to serve the live preview on {addr}: {e}");
+                            opened = false;
+                        }
+                    },
+                    #[cfg(feature = "nixie")]
+                    Output::Nixie { serial_port } => match nixie::Nixie::new(serial_port) {
+                        Ok(nixie) => drivers.add_dimmed(
+                            configure_nixie(nixie, &config),
+                            reloader.nixie_brightness(),
+                        ),
+                        Err(e) => {
+                            log::error!("{e:#}");
+                            opened = false;
+                        }
+                    },
+                }
+            }
+            if opened {
+                run(drivers, reloader, &config, std::future::pending::<()>()).await
+            } else {
+                // release the outputs opened so far
+                drivers.run_until(async {}).await;
+                ExitCode::FAILURE
             }
         }
-    }
+    };
+
+    log::logger().flush();
+    code
 }
@@ -1,13 +1,17 @@
+use std::collections::VecDeque;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use super::{
-    price_queue::{PlotKind, PriceQueue},
+    price_queue::{Direction, PlotKind, PriceQueue},
     screen::{
         character::{Character, Font},
+        face::{Face, Widget},
+        palette::Palette,
         rgb::{colorize, Rgb888},
+        transition::Transition,
         Screen,
     },
-    ws_coin::{Market, Price, WsCoin},
+    ws_coin::{ConnectionState, Market, Price, Source, WsCoin},
 };
 use futures::{Stream, StreamExt as _};
 use rust_decimal::prelude::*;
@@ -40,11 +44,73 @@ async fn wait_for_round_second() {
     tokio::time::sleep(Duration::from_nanos(ns)).await;
 }
 
+/// What the matrix shows, and how
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Layout {
+    /// Animation between two consecutive prices
+    pub transition: Transition,
+    pub price_font: Font,
+    pub time_font: Font,
+    /// strftime-like format of the clock
+    pub time_format: String,
+    pub plot: PlotKind,
+    /// Blink the status dot on each new price
+    pub indicator: bool,
+    /// Where the widgets go
+    pub face: Face,
+    /// Size of the matrix, in pixels; 32x8 on Awtrix
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Layout {
+    /// Check what would panic on rendering: a matrix without pixels, or a `time_format` that can't
+    /// be formatted
+    pub fn validate(&self) -> Result<(), String> {
+        for (key, size) in [("width", self.width), ("height", self.height)] {
+            if !(1..=256).contains(&size) {
+                return Err(format!("{key} must be between 1 and 256"));
+            }
+        }
+        if chrono::format::StrftimeItems::new(&self.time_format)
+            .any(|item| item == chrono::format::Item::Error)
+        {
+            return Err(format!("invalid time_format {:?}", self.time_format));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            transition: Transition::None,
+            price_font: Font::Medium,
+            time_font: Font::Small,
+            time_format: "%H:%M:%S".to_string(),
+            plot: PlotKind::FlatLine,
+            indicator: true,
+            face: Face::classic(),
+            width: 32,
+            height: 8,
+        }
+    }
+}
+
+/// The price of the first market over its chart, and the time.
 pub struct BtcTimeMatrix {
+    markets: Vec<Market>,
     pq: PriceQueue,
     ws_coin: WsCoin,
     price: Option<Decimal>,
+    latest: tokio::sync::watch::Sender<Option<Price>>,
+    prices: tokio::sync::broadcast::Sender<Price>,
     indicator_lit: bool, // a "network activity" indicator at bottom-left corner
+    layout: Layout,
+    palette: Palette,
+    frame_interval: Duration,
+    price_frames: VecDeque<Character>, // pending renderings of the price while it transitions
 }
 
 impl BtcTimeMatrix {
@@ -53,57 +119,173 @@ impl BtcTimeMatrix {
             symbol: "BTCUSDT".to_string(),
             name: "BTC".to_string(),
         }];
+        Self::new(markets, Source::default()).await
+    }
+    /// Follow every market of `markets`, showing the first one
+    pub async fn new(markets: Vec<Market>, source: Source) -> Self {
+        assert!(!markets.is_empty(), "no market to show");
         BtcTimeMatrix {
+            markets: markets.clone(),
             pq: PriceQueue::default(),
-            ws_coin: WsCoin::new(markets).await,
+            ws_coin: WsCoin::with_source(markets, source).await,
             price: None,
+            latest: tokio::sync::watch::Sender::new(None),
+            prices: tokio::sync::broadcast::Sender::new(64),
             indicator_lit: false,
+            layout: Layout::default(),
+            palette: Palette::default(),
+            frame_interval: Duration::ZERO,
+            price_frames: VecDeque::new(),
         }
     }
+    pub fn connection_state(&self) -> tokio::sync::watch::Receiver<ConnectionState> {
+        self.ws_coin.state()
+    }
+    pub fn markets(&self) -> &[Market] {
+        &self.markets
+    }
+    /// The last price received, as shown on the matrix
+    pub fn latest_price(&self) -> tokio::sync::watch::Receiver<Option<Price>> {
+        self.latest.subscribe()
+    }
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.pq.resize(layout.width);
+        self.layout = layout;
+        self
+    }
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+    /// Follow `markets` instead, without reconnecting. The price history is kept unless another
+    /// market is shown.
+    pub fn set_markets(&mut self, markets: Vec<Market>) {
+        assert!(!markets.is_empty(), "no market to show");
+        if markets[0].symbol != self.markets[0].symbol {
+            self.pq = PriceQueue::with_capacity(self.layout.width);
+            self.price = None;
+            self.price_frames.clear();
+            self.latest.send_replace(None);
+        }
+        self.ws_coin.set_markets(markets.clone());
+        self.markets = markets;
+    }
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.pq.resize(layout.width); // the chart is at most as wide as the matrix
+        self.layout = layout;
+    }
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+    /// Every price received, of every market, as it's received
+    pub fn subscribe_prices(&self) -> tokio::sync::broadcast::Receiver<Price> {
+        self.prices.subscribe()
+    }
+    /// Animate price changes with `transition`, showing each intermediate frame for `frame_interval`.
+    pub fn with_transition(mut self, transition: Transition, frame_interval: Duration) -> Self {
+        self.layout.transition = transition;
+        self.frame_interval = frame_interval;
+        self
+    }
     pub async fn gen_screen(&mut self) -> Screen {
-        let ws_coin = self.ws_coin.subscribe();
-        tokio::pin!(ws_coin);
-        tokio::select! {
-            Some(price) = ws_coin.next() => {
-                self.price = Some(price.price);
-                self.pq.push(price.price);
+        if !self.price_frames.is_empty() {
+            // keep the price feed waiting until the running transition finishes
+            tokio::time::sleep(self.frame_interval).await;
+        } else {
+            let ws_coin = self.ws_coin.subscribe();
+            tokio::pin!(ws_coin);
+            tokio::select! {
+                Some(price) = ws_coin.next() => {
+                    let _ = self.prices.send(price.clone()); // nobody may be listening
+                    // the other markets are only followed, not shown
+                    if price.symbol == self.markets[0].symbol {
+                        let font = self.layout.price_font;
+                        if let Some(prev) = self.price {
+                            self.price_frames.extend(self.layout.transition.frames(
+                                &Character::float_to_string(prev, font),
+                                &Character::float_to_string(price.price, font),
+                                font,
+                            ));
+                        }
+                        self.price = Some(price.price);
+                        self.pq.push(price.price);
+                        self.latest.send_replace(Some(price));
 
-                self.indicator_lit = !self.indicator_lit; // toggle the indicator on new price
-            },
-            _ = wait_for_round_second() => {
-                self.indicator_lit = false; // turn off the indicator at each second
+                        self.indicator_lit = !self.indicator_lit; // toggle the indicator on new price
+                    }
+                },
+                _ = wait_for_round_second() => {
+                    self.indicator_lit = false; // turn off the indicator at each second
+                }
             }
         }
 
-        let mut screen = self.pq.to_screen(PlotKind::FlatLine, false);
-        if self.price.is_some() {
-            let major_cs = Character::from_float(self.price.unwrap(), Font::Medium);
-            screen.draw(
-                &colorize(
-                    &major_cs.pixels,
-                    &Rgb888::new(255, 255, 255),
-                    &Rgb888::new(255, 255, 0),
-                ),
-                32usize.saturating_sub(major_cs.pixels[0].len() + 1),
-                0,
-            );
-        }
-
-        let minor_cs = Character::from_time(Font::Small);
-        screen.draw(
-            &colorize(
-                &minor_cs.pixels,
-                &Rgb888::new(255, 255, 255),
-                &Rgb888::new(200, 200, 200),
-            ),
-            32usize.saturating_sub(minor_cs.pixels[0].len() + 1),
-            5,
-        );
+        self.render()
+    }
+    fn render(&mut self) -> Screen {
+        let white = Rgb888::new(255, 255, 255);
+        let text = |text: &str, font, color| {
+            Some(colorize(
+                &Character::from_string(text, font).pixels,
+                &white,
+                color,
+            ))
+        };
+        let price = self.price_frames.pop_front().or_else(|| {
+            self.price
+                .map(|p| Character::from_float(p, self.layout.price_font))
+        });
+        let trend = self
+            .pq
+            .to_up_down()
+            .last()
+            .copied()
+            .unwrap_or(Direction::Flat);
+        let latest = self.latest.borrow().clone();
+        let connection = self.ws_coin.state().borrow().clone();
+        let (layout, palette) = (&self.layout, &self.palette);
 
-        if self.indicator_lit {
-            screen.draw(&[vec![Some(Rgb888::new(255, 255, 0))]], 0, 7);
+        let (width, height) = (layout.width, layout.height);
+        let mut screen = layout
+            .face
+            .compose(width, height, |region| match region.widget {
+                Widget::Price => price
+                    .as_ref()
+                    .map(|cs| colorize(&cs.pixels, &white, &palette.price)),
+                Widget::Clock => {
+                    let time = chrono::Local::now().format(&layout.time_format);
+                    text(&time.to_string(), layout.time_font, &palette.time)
+                }
+                Widget::Chart => {
+                    let (w, h) = region.size(width, height);
+                    (w > 0 && h > 0)
+                        .then(|| self.pq.to_colored_screen(layout.plot, palette, w, h).pixels)
+                }
+                Widget::Ticker => text(&self.markets[0].name, Font::Large, &palette.time),
+                Widget::Icon => Some(region.icon.pixels(trend, palette)),
+                Widget::Change => {
+                    let change = latest.as_ref()?.change_24h?.round_dp(1);
+                    let (sign, color) = match change.cmp(&Decimal::ZERO) {
+                        std::cmp::Ordering::Greater => ("+", &palette.up),
+                        std::cmp::Ordering::Less => ("", &palette.down),
+                        std::cmp::Ordering::Equal => ("", &palette.flat),
+                    };
+                    text(&format!("{sign}{change:.1}%"), layout.time_font, color)
+                }
+                Widget::Status => match connection {
+                    _ if !layout.indicator => None,
+                    ConnectionState::Connected if self.indicator_lit => {
+                        Some(vec![vec![Some(palette.indicator)]])
+                    }
+                    ConnectionState::Disconnected(_) => Some(vec![vec![Some(palette.down)]]),
+                    _ => None,
+                },
+            });
+        if palette.background != Rgb888::new(0, 0, 0) {
+            for pixel in screen.pixels.iter_mut().flatten() {
+                pixel.get_or_insert(palette.background);
+            }
         }
-
         screen
     }
     pub fn subscribe(&mut self) -> impl Stream<Item = Screen> + '_ {
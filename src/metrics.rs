@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::{http::header, routing::get, Router};
+use rust_decimal::prelude::*;
+
+use super::ws_coin::Price;
+
+/// Counters of the whole process, served in the Prometheus text format
+pub static METRICS: Metrics = Metrics::new();
+
+#[derive(Default)]
+struct MarketStats {
+    received: u64,
+    last_price: Decimal,
+}
+
+#[derive(Default)]
+struct OutputStats {
+    rendered: u64,
+    dropped: u64,
+}
+
+#[derive(Default)]
+struct RequestStats {
+    count: u64,
+    seconds: f64,
+    failures: u64,
+}
+
+pub struct Metrics {
+    markets: Mutex<BTreeMap<String, MarketStats>>,
+    last_price_at: Mutex<Option<Instant>>,
+    parse_errors: AtomicU64,
+    reconnects: AtomicU64,
+    outputs: Mutex<BTreeMap<String, OutputStats>>,
+    awtrix_requests: Mutex<BTreeMap<String, RequestStats>>, // per host
+    nixie_write_errors: AtomicU64,
+}
+
+impl Metrics {
+    pub const fn new() -> Self {
+        Metrics {
+            markets: Mutex::new(BTreeMap::new()),
+            last_price_at: Mutex::new(None),
+            parse_errors: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+            outputs: Mutex::new(BTreeMap::new()),
+            awtrix_requests: Mutex::new(BTreeMap::new()),
+            nixie_write_errors: AtomicU64::new(0),
+        }
+    }
+
+    pub fn price_received(&self, price: &Price) {
+        let mut markets = self.markets.lock().unwrap();
+        let market = markets.entry(price.name.clone()).or_default();
+        market.received += 1;
+        market.last_price = price.price;
+        *self.last_price_at.lock().unwrap() = Some(Instant::now());
+    }
+
+    pub fn parse_error(&self) {
+        self.parse_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn reconnect(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn frame_rendered(&self, output: &str) {
+        let mut outputs = self.outputs.lock().unwrap();
+        outputs.entry(output.to_string()).or_default().rendered += 1;
+    }
+
+    /// `count` frames skipped by `output`, being too slow for them or rate limited
+    pub fn frames_dropped(&self, output: &str, count: u64) {
+        let mut outputs = self.outputs.lock().unwrap();
+        outputs.entry(output.to_string()).or_default().dropped += count;
+    }
+
+    pub fn awtrix_request(&self, host: &str, took: Duration, succeeded: bool) {
+        let mut requests = self.awtrix_requests.lock().unwrap();
+        let stats = requests.entry(host.to_string()).or_default();
+        stats.count += 1;
+        stats.seconds += took.as_secs_f64();
+        if !succeeded {
+            stats.failures += 1;
+        }
+    }
+
+    pub fn nixie_write_error(&self) {
+        self.nixie_write_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Everything, in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut family = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{name}{labels} {value}");
+            }
+        };
+
+        {
+            let markets = self.markets.lock().unwrap();
+            family(
+                "cyberpunk_prices_received_total",
+                "counter",
+                "Trades received, per market",
+                markets
+                    .iter()
+                    .map(|(name, m)| (labels(&[("market", name)]), m.received.to_string()))
+                    .collect(),
+            );
+            family(
+                "cyberpunk_last_price",
+                "gauge",
+                "Price of the last trade received, per market",
+                markets
+                    .iter()
+                    .map(|(name, m)| {
+                        let price = m.last_price.to_f64().unwrap_or(f64::NAN);
+                        (labels(&[("market", name)]), price.to_string())
+                    })
+                    .collect(),
+            );
+        }
+        family(
+            "cyberpunk_seconds_since_last_price",
+            "gauge",
+            "Time since the last trade was received, absent before the first one",
+            self.last_price_at
+                .lock()
+                .unwrap()
+                .map(|at| (String::new(), at.elapsed().as_secs_f64().to_string()))
+                .into_iter()
+                .collect(),
+        );
+        family(
+            "cyberpunk_parse_errors_total",
+            "counter",
+            "Messages from Binance that couldn't be parsed",
+            vec![(
+                String::new(),
+                self.parse_errors.load(Ordering::Relaxed).to_string(),
+            )],
+        );
+        family(
+            "cyberpunk_reconnects_total",
+            "counter",
+            "Reconnections to Binance, after an error or a timeout",
+            vec![(
+                String::new(),
+                self.reconnects.load(Ordering::Relaxed).to_string(),
+            )],
+        );
+
+        {
+            let outputs = self.outputs.lock().unwrap();
+            family(
+                "cyberpunk_frames_rendered_total",
+                "counter",
+                "Frames rendered successfully, per output",
+                outputs
+                    .iter()
+                    .map(|(name, o)| (labels(&[("output", name)]), o.rendered.to_string()))
+                    .collect(),
+            );
+            family(
+                "cyberpunk_frames_dropped_total",
+                "counter",
+                "Frames skipped by an output too slow or rate limited, per output",
+                outputs
+                    .iter()
+                    .map(|(name, o)| (labels(&[("output", name)]), o.dropped.to_string()))
+                    .collect(),
+            );
+        }
+
+        {
+            let requests = self.awtrix_requests.lock().unwrap();
+            let per_host = |value: fn(&RequestStats) -> String| {
+                requests
+                    .iter()
+                    .map(|(host, stats)| (labels(&[("host", host)]), value(stats)))
+                    .collect()
+            };
+            family(
+                "cyberpunk_awtrix_requests_total",
+                "counter",
+                "Requests sent to Awtrix, per host",
+                per_host(|s| s.count.to_string()),
+            );
+            family(
+                "cyberpunk_awtrix_request_seconds_total",
+                "counter",
+                "Total time spent on requests to Awtrix, per host",
+                per_host(|s| s.seconds.to_string()),
+            );
+            family(
+                "cyberpunk_awtrix_request_failures_total",
+                "counter",
+                "Requests to Awtrix that failed or timed out, per host",
+                per_host(|s| s.failures.to_string()),
+            );
+        }
+        family(
+            "cyberpunk_nixie_write_errors_total",
+            "counter",
+            "Failed writes to the nixie serial port",
+            vec![(
+                String::new(),
+                self.nixie_write_errors.load(Ordering::Relaxed).to_string(),
+            )],
+        );
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `{name="value",...}`, escaped as the text format requires
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let pairs: Vec<String> = pairs
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', r"\\")
+                .replace('"', r#"\""#)
+                .replace('\n', r"\n");
+            format!("{name}=\"{value}\"")
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// Serve [`METRICS`] on `addr` at `/metrics`, until the returned task is aborted.
+pub async fn serve(
+    addr: SocketAddr,
+) -> anyhow::Result<tokio::task::JoinHandle<std::io::Result<()>>> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    log::info!(
+        "Serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+    let app = Router::new().route(
+        "/metrics",
+        get(|| async {
+            (
+                [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                METRICS.render(),
+            )
+        }),
+    );
+    Ok(tokio::spawn(
+        async move { axum::serve(listener, app).await },
+    ))
+}
+
+#[test]
+fn test_metrics() {
+    use rust_decimal_macros::dec;
+
+    let metrics = Metrics::new();
+    // no trade yet, so no staleness to tell
+    assert!(!metrics
+        .render()
+        .lines()
+        .any(|l| l.starts_with("cyberpunk_seconds_since_last_price ")));
+    for price in [dec!(100000.5), dec!(100001)] {
+        metrics.price_received(&Price {
+            symbol: "BTCUSDT".to_string(),
+            name: "BTC".to_string(),
+            price,
+            change_24h: None,
+        });
+    }
+    metrics.parse_error();
+    metrics.frame_rendered("Awtrix at clock:80");
+    metrics.frames_dropped("Awtrix at clock:80", 3);
+    metrics.awtrix_request("clock", Duration::from_millis(250), false);
+    metrics.awtrix_request("clock", Duration::from_millis(250), true);
+    metrics.frame_rendered("the \"quoted\" one");
+
+    let text = metrics.render();
+    for line in [
+        "# TYPE cyberpunk_prices_received_total counter",
+        "cyberpunk_prices_received_total{market=\"BTC\"} 2",
+        "cyberpunk_last_price{market=\"BTC\"} 100001",
+        "cyberpunk_parse_errors_total 1",
+        "cyberpunk_reconnects_total 0",
+        "cyberpunk_frames_rendered_total{output=\"Awtrix at clock:80\"} 1",
+        "cyberpunk_frames_dropped_total{output=\"Awtrix at clock:80\"} 3",
+        "cyberpunk_frames_rendered_total{output=\"the \\\"quoted\\\" one\"} 1",
+        "cyberpunk_awtrix_requests_total{host=\"clock\"} 2",
+        "cyberpunk_awtrix_request_seconds_total{host=\"clock\"} 0.5",
+        "cyberpunk_awtrix_request_failures_total{host=\"clock\"} 1",
+        "cyberpunk_nixie_write_errors_total 0",
+    ] {
+        assert!(text.lines().any(|l| l == line), "{line} not in\n{text}");
+    }
+    assert!(text
+        .lines()
+        .any(|l| l.starts_with("cyberpunk_seconds_since_last_price 0.")));
+}
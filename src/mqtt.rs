@@ -0,0 +1,414 @@
+use std::future::Future;
+use std::time::Duration;
+
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+
+use super::{
+    awtrix::custom_app,
+    feed::Frame,
+    screen::Screen,
+    sink::DisplaySink,
+    ws_coin::{Market, Price},
+};
+
+/// Something MQTT messages can be published to: the broker connection, or a test double.
+pub trait Publisher {
+    fn publish(
+        &self,
+        topic: String,
+        payload: Vec<u8>,
+        retain: bool,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+}
+
+/// A connection to an MQTT broker, kept alive by a background task.
+pub struct MqttClient {
+    client: AsyncClient,
+    poller: tokio::task::JoinHandle<()>,
+}
+
+impl MqttClient {
+    pub fn connect(
+        host: &str,
+        port: u16,
+        client_id: &str,
+        credentials: Option<(String, String)>,
+    ) -> Self {
+        let mut options = MqttOptions::new(client_id, host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some((username, password)) = credentials {
+            options.set_credentials(username, password);
+        }
+        let (client, mut eventloop) = AsyncClient::new(options, 64);
+
+        let poller = tokio::spawn(async move {
+            loop {
+                match eventloop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        log::info!("Connected to MQTT broker")
+                    }
+                    Ok(Event::Outgoing(rumqttc::Outgoing::Disconnect)) => return,
+                    Ok(_) => {}
+                    Err(e) => {
+                        log::error!("MQTT connection error: {e}, retrying in 5s...");
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+        MqttClient { client, poller }
+    }
+
+    /// Disconnect once the messages published so far are sent.
+    pub async fn disconnect(&mut self) {
+        if self.client.disconnect().await.is_ok() {
+            let _ = tokio::time::timeout(Duration::from_secs(5), &mut self.poller).await;
+        }
+    }
+}
+
+impl Publisher for MqttClient {
+    async fn publish(&self, topic: String, payload: Vec<u8>, retain: bool) -> anyhow::Result<()> {
+        // don't hold up the display while the broker is unreachable and the queue is full
+        Ok(self
+            .client
+            .try_publish(topic, QoS::AtLeastOnce, retain, payload)?)
+    }
+}
+
+pub struct MqttTopics {
+    /// Topic prefix of the Awtrix 3 clock, as set in its MQTT settings
+    pub awtrix_prefix: String,
+    /// Name of the custom app showing the matrix on the clock
+    pub app_name: String,
+    /// Prefix of the price and change topics, `<state_prefix>/<market>/price` etc.
+    pub state_prefix: String,
+    /// Home Assistant discovery prefix, if the sensors should be announced
+    pub discovery_prefix: Option<String>,
+}
+
+/// Publishes rendered screens as an Awtrix 3 custom app, and prices for Home Assistant.
+pub struct MqttSink<P: Publisher> {
+    publisher: P,
+    topics: MqttTopics,
+    announced: Vec<Market>,
+    size: (usize, usize), // of the last frame, to clear as much
+}
+
+impl<P: Publisher> MqttSink<P> {
+    pub fn new(publisher: P, topics: MqttTopics) -> Self {
+        MqttSink {
+            publisher,
+            topics,
+            announced: Vec::new(),
+            size: Screen::default().size(),
+        }
+    }
+
+    async fn publish(&self, topic: String, payload: Vec<u8>, retain: bool) {
+        if let Err(e) = self.publisher.publish(topic.clone(), payload, retain).await {
+            log::warn!("Failed to publish to {topic}: {e}");
+        }
+    }
+
+    fn state_topic(&self, market_name: &str, field: &str) -> String {
+        format!(
+            "{}/{}/{field}",
+            self.topics.state_prefix,
+            market_name.to_lowercase()
+        )
+    }
+
+    /// Home Assistant discovery topic of the `field` sensor of `market`, if they're announced
+    fn discovery_topic(&self, market: &Market, field: &str) -> Option<String> {
+        let discovery_prefix = self.topics.discovery_prefix.as_ref()?;
+        Some(format!(
+            "{discovery_prefix}/sensor/{}/{}_{field}/config",
+            self.topics.state_prefix,
+            market.name.to_lowercase()
+        ))
+    }
+
+    /// Announce the price and change sensors of `market` through Home Assistant discovery.
+    pub async fn announce(&self, market: &Market) {
+        let node_id = &self.topics.state_prefix;
+        let name = market.name.to_lowercase();
+        // e.g. "USDT" for BTCUSDT
+        let quote = market.symbol.strip_prefix(&market.name).unwrap_or("");
+        for (field, label, unit, icon) in [
+            ("price", "price", quote, "mdi:currency-usd"),
+            ("change_24h", "24h change", "%", "mdi:percent"),
+        ] {
+            let config = serde_json::json!({
+                "name": format!("{} {label}", market.name),
+                "unique_id": format!("{node_id}_{name}_{field}"),
+                "state_topic": self.state_topic(&market.name, field),
+                "unit_of_measurement": unit,
+                "state_class": "measurement",
+                "icon": icon,
+                "device": {
+                    "identifiers": [node_id],
+                    "name": "Cyberpunk Display",
+                    "model": "cyberpunk_display",
+                },
+            });
+            if let Some(topic) = self.discovery_topic(market, field) {
+                self.publish(topic, config.to_string().into_bytes(), true)
+                    .await;
+            }
+        }
+    }
+
+    /// Remove the sensors of `market` from Home Assistant, e.g. once it's no longer followed
+    pub async fn retract(&self, market: &Market) {
+        for field in ["price", "change_24h"] {
+            if let Some(topic) = self.discovery_topic(market, field) {
+                self.publish(topic, Vec::new(), true).await;
+            }
+        }
+    }
+
+    /// Announce the markets added to `markets` or changed since last time, e.g. by a reload, and
+    /// retract those removed
+    pub async fn follow(&mut self, markets: &[Market]) {
+        for market in &self.announced {
+            if !markets.iter().any(|m| m.name == market.name) {
+                self.retract(market).await;
+            }
+        }
+        for market in markets {
+            if !self.announced.contains(market) {
+                self.announce(market).await;
+            }
+        }
+        self.announced = markets.to_vec();
+    }
+
+    pub async fn plot(&self, screen: &Screen) {
+        self.publish(
+            format!(
+                "{}/custom/{}",
+                self.topics.awtrix_prefix, self.topics.app_name
+            ),
+            custom_app(screen).to_string().into_bytes(),
+            false,
+        )
+        .await
+    }
+
+    /// Set the brightness of the clock, from 0 to 255, turning off its auto-brightness
+    pub async fn set_brightness(&self, brightness: u8) {
+        self.publish(
+            format!("{}/settings", self.topics.awtrix_prefix),
+            serde_json::json!({ "ABRI": false, "BRI": brightness })
+                .to_string()
+                .into_bytes(),
+            false,
+        )
+        .await
+    }
+
+    pub async fn publish_price(&self, price: &Price) {
+        self.publish(
+            self.state_topic(&price.name, "price"),
+            price.price.to_string().into_bytes(),
+            true,
+        )
+        .await;
+        if let Some(change_24h) = price.change_24h {
+            self.publish(
+                self.state_topic(&price.name, "change_24h"),
+                change_24h.to_string().into_bytes(),
+                true,
+            )
+            .await;
+        }
+    }
+
+    /// Publish the message of an alert that fired to `<state_prefix>/alert`
+    pub async fn publish_alert(&self, message: &str) {
+        self.publish(
+            format!("{}/alert", self.topics.state_prefix),
+            message.as_bytes().to_vec(),
+            false,
+        )
+        .await
+    }
+
+    /// Remove the custom app from the clock
+    pub async fn exit(&self) {
+        self.publish(
+            format!(
+                "{}/custom/{}",
+                self.topics.awtrix_prefix, self.topics.app_name
+            ),
+            Vec::new(),
+            false,
+        )
+        .await
+    }
+}
+
+impl DisplaySink for MqttSink<MqttClient> {
+    fn name(&self) -> String {
+        "MQTT broker".to_string()
+    }
+
+    async fn render(&mut self, frame: &Frame) -> anyhow::Result<()> {
+        self.size = frame.screen.size();
+        self.plot(&frame.screen).await;
+        Ok(())
+    }
+
+    // every market announced gets its price, not only the one shown
+    async fn price(&mut self, price: &Price) -> anyhow::Result<()> {
+        self.publish_price(price).await;
+        Ok(())
+    }
+
+    async fn markets(&mut self, markets: &[Market]) -> anyhow::Result<()> {
+        self.follow(markets).await;
+        Ok(())
+    }
+
+    async fn set_brightness(&mut self, brightness: u8) -> anyhow::Result<()> {
+        MqttSink::set_brightness(self, brightness).await;
+        Ok(())
+    }
+
+    async fn alert(&mut self, message: &str) -> anyhow::Result<()> {
+        self.publish_alert(message).await;
+        Ok(())
+    }
+
+    async fn clear(&mut self) -> anyhow::Result<()> {
+        let (width, height) = self.size;
+        self.plot(&Screen::new(width, height)).await;
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.exit().await;
+        self.publisher.disconnect().await;
+        log::info!("Disconnected from MQTT broker");
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_mqtt_sink() {
+    use rust_decimal_macros::dec;
+    use std::sync::Mutex;
+
+    /// An in-process stand-in for the broker, keeping every message as (topic, payload, retain)
+    #[derive(Default)]
+    struct Broker(Mutex<Vec<(String, String, bool)>>);
+
+    impl Publisher for &Broker {
+        async fn publish(
+            &self,
+            topic: String,
+            payload: Vec<u8>,
+            retain: bool,
+        ) -> anyhow::Result<()> {
+            let payload = String::from_utf8(payload)?;
+            self.0.lock().unwrap().push((topic, payload, retain));
+            Ok(())
+        }
+    }
+
+    let broker = Broker::default();
+    let mut sink = MqttSink::new(
+        &broker,
+        MqttTopics {
+            awtrix_prefix: "awtrix_1a2b3c".to_string(),
+            app_name: "cyberpunk".to_string(),
+            state_prefix: "cyberpunk_display".to_string(),
+            discovery_prefix: Some("homeassistant".to_string()),
+        },
+    );
+    let btc = Market {
+        symbol: "BTCUSDT".to_string(),
+        name: "BTC".to_string(),
+    };
+    sink.follow(std::slice::from_ref(&btc)).await;
+    sink.plot(&Screen::default()).await;
+    sink.publish_price(&Price {
+        symbol: "BTCUSDT".to_string(),
+        name: "BTC".to_string(),
+        price: dec!(94395.23),
+        change_24h: Some(dec!(-1.25)),
+    })
+    .await;
+    sink.exit().await;
+
+    let messages = broker.0.lock().unwrap().clone();
+    let topics: Vec<&str> = messages.iter().map(|(t, _, _)| t.as_str()).collect();
+    assert_eq!(
+        topics,
+        [
+            "homeassistant/sensor/cyberpunk_display/btc_price/config",
+            "homeassistant/sensor/cyberpunk_display/btc_change_24h/config",
+            "awtrix_1a2b3c/custom/cyberpunk",
+            "cyberpunk_display/btc/price",
+            "cyberpunk_display/btc/change_24h",
+            "awtrix_1a2b3c/custom/cyberpunk",
+        ]
+    );
+
+    let config: serde_json::Value = serde_json::from_str(&messages[0].1).unwrap();
+    assert_eq!(config["state_topic"], "cyberpunk_display/btc/price");
+    assert_eq!(config["unit_of_measurement"], "USDT");
+    assert_eq!(config["unique_id"], "cyberpunk_display_btc_price");
+    assert!(messages[0].2, "discovery configs must be retained");
+
+    let app: serde_json::Value = serde_json::from_str(&messages[2].1).unwrap();
+    assert_eq!(app["draw"][0]["db"][4].as_array().unwrap().len(), 32 * 8);
+
+    assert_eq!(messages[3].1, "94395.23");
+    assert_eq!(messages[4].1, "-1.25");
+    assert_eq!(messages[5].1, "");
+
+    // reloaded with ETH added, then BTC removed
+    let eth = Market {
+        symbol: "ETHUSDT".to_string(),
+        name: "ETH".to_string(),
+    };
+    sink.follow(&[btc, eth.clone()]).await;
+    sink.follow(&[eth]).await;
+    sink.set_brightness(30).await;
+    let messages = broker.0.lock().unwrap();
+    assert_eq!(
+        messages[10],
+        (
+            "awtrix_1a2b3c/settings".to_string(),
+            r#"{"ABRI":false,"BRI":30}"#.to_string(),
+            false
+        )
+    );
+    let retained: Vec<(&str, bool)> = messages[6..10]
+        .iter()
+        .map(|(t, p, _)| (t.as_str(), p.is_empty()))
+        .collect();
+    assert_eq!(
+        retained,
+        [
+            (
+                "homeassistant/sensor/cyberpunk_display/eth_price/config",
+                false
+            ),
+            (
+                "homeassistant/sensor/cyberpunk_display/eth_change_24h/config",
+                false
+            ),
+            (
+                "homeassistant/sensor/cyberpunk_display/btc_price/config",
+                true
+            ),
+            (
+                "homeassistant/sensor/cyberpunk_display/btc_change_24h/config",
+                true
+            ),
+        ]
+    );
+}
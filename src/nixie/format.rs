@@ -0,0 +1,235 @@
+use rust_decimal::prelude::*;
+use serde::Deserialize;
+
+use super::NixieMsg;
+
+/// Shown on every tube for a number that doesn't fit, or is negative
+pub const OVERFLOW: u8 = b'-';
+
+/// Where a number shorter than the tubes sits
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    /// The tubes left over unlit on the right
+    #[default]
+    Left,
+    /// Zeros on the left, or unlit tubes with `blank_leading_zeros`
+    Right,
+}
+
+/// How prices are laid out on the tubes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NumberFormat {
+    /// Decimals shown, fewer if the number wouldn't fit otherwise; as many as fit if unset
+    pub decimals: Option<u32>,
+    /// Thousands, e.g. 104521.3 as "104.52" followed by a lit decimal point standing for "k"
+    pub thousands: bool,
+    /// Leave the zeros padding a right aligned number unlit
+    pub blank_leading_zeros: bool,
+    pub align: Align,
+}
+
+impl NumberFormat {
+    /// `num` over `tubes` digits
+    pub fn format(&self, num: Decimal, tubes: usize) -> NixieMsg {
+        // the "k" point takes a tube of its own
+        let (value, width, suffix) = if self.thousands {
+            (num / Decimal::ONE_THOUSAND, tubes - 1, "k")
+        } else {
+            (num, tubes, "")
+        };
+        let Some((int, frac)) = self.fit(value, width) else {
+            let overflow = char::from(OVERFLOW).to_string().repeat(tubes);
+            return NixieMsg::new(overflow.clone(), &overflow, vec![false; tubes]);
+        };
+
+        let shown = int.len() + frac.len();
+        let (left, right) = match self.align {
+            Align::Left => (0, tubes - shown),
+            Align::Right => (width - shown, tubes - width),
+        };
+        let padding = if self.blank_leading_zeros { " " } else { "0" };
+        let digits = format!("{}{int}{frac}{}", padding.repeat(left), " ".repeat(right));
+        let mut dots = vec![false; tubes];
+        if !frac.is_empty() {
+            dots[left + int.len()] = true;
+        }
+        if self.thousands {
+            dots[left + shown] = true;
+        }
+        let text = match frac.is_empty() {
+            true => format!("{int}{suffix}"),
+            false => format!("{int}.{frac}{suffix}"),
+        };
+        NixieMsg::new(text, &digits, dots)
+    }
+
+    /// The digits of `value` before and after the decimal point, `width` of them at most. The
+    /// integer part of a number under 1 is left out for one more decimal, as in ".1513".
+    fn fit(&self, value: Decimal, width: usize) -> Option<(String, String)> {
+        if value < Decimal::ZERO {
+            return None;
+        }
+        let int_len = value.trunc().to_string().len();
+        let mut decimals = self
+            .decimals
+            .unwrap_or(u32::MAX)
+            .min(width.saturating_sub(int_len) as u32 + 1);
+        loop {
+            let rounded = value.round_dp(decimals).abs();
+            let int = rounded.trunc();
+            let int = match int.is_zero() && decimals > 0 {
+                true => String::new(),
+                false => int.to_string(),
+            };
+            if int.len() + decimals as usize <= width {
+                let shown = format!("{rounded:.0$}", decimals as usize);
+                let frac = shown.split_once('.').map_or("", |(_, frac)| frac);
+                return Some((int, frac.to_string()));
+            }
+            // rounding up may have made the integer part longer
+            decimals = decimals
+                .checked_sub(1)?
+                .min(width.saturating_sub(int.len()) as u32);
+        }
+    }
+}
+
+#[test]
+fn test_number_format() {
+    use rust_decimal_macros::dec;
+
+    let show = |format: NumberFormat, num| {
+        let msg = format.format(num, 6);
+        let dots: String = msg
+            .dots
+            .iter()
+            .map(|&d| if d { '.' } else { ' ' })
+            .collect();
+        (String::from_utf8(msg.digits).unwrap(), dots, msg.text)
+    };
+    let fixed = NumberFormat {
+        decimals: Some(2),
+        ..NumberFormat::default()
+    };
+    assert_eq!(
+        show(fixed, dec!(100.2)),
+        ("10020 ".into(), "   .  ".into(), "100.20".into())
+    );
+    let right = NumberFormat {
+        align: Align::Right,
+        ..fixed
+    };
+    assert_eq!(show(right, dec!(5.3)).0, "000530");
+    let blanked = NumberFormat {
+        blank_leading_zeros: true,
+        ..right
+    };
+    assert_eq!(show(blanked, dec!(5.3)).0, "   530");
+    assert_eq!(show(blanked, dec!(0.25)).0, "    25");
+    // decimals dropped to fit
+    assert_eq!(show(fixed, dec!(94395.23)).0, "943952");
+
+    let thousands = NumberFormat {
+        thousands: true,
+        ..NumberFormat::default()
+    };
+    assert_eq!(
+        show(thousands, dec!(104521.3)),
+        ("10452 ".into(), "   . .".into(), "104.52k".into())
+    );
+    assert_eq!(
+        show(
+            NumberFormat {
+                decimals: Some(1),
+                ..thousands
+            },
+            dec!(104521.3)
+        ),
+        ("1045  ".into(), "   .. ".into(), "104.5k".into())
+    );
+    assert_eq!(show(thousands, dec!(999999999)).0, "------");
+
+    assert_eq!(show(NumberFormat::default(), dec!(999999.5)).0, "------");
+    assert_eq!(show(NumberFormat::default(), dec!(-1)).0, "------");
+    assert_eq!(show(NumberFormat::default(), dec!(0)).0, "000000");
+    assert_eq!(show(NumberFormat::default(), dec!(0.9999999)).0, "100000");
+}
+
+#[test]
+fn test_number_format_random() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // a failure is replayed with the seed it reports, e.g. `NUMBER_FORMAT_SEED=42 cargo test`
+    let seed: u64 = match std::env::var("NUMBER_FORMAT_SEED") {
+        Ok(seed) => seed.parse().expect("NUMBER_FORMAT_SEED should be a number"),
+        Err(_) => rand::random(),
+    };
+    let mut rng = StdRng::seed_from_u64(seed);
+    for _ in 0..10_000 {
+        let num = Decimal::new(
+            rng.gen_range(0..i64::MAX) >> rng.gen_range(0..63),
+            rng.gen_range(0..=12),
+        );
+        let tubes = rng.gen_range(4..=10);
+        let format = NumberFormat {
+            decimals: rng.gen_bool(0.5).then(|| rng.gen_range(0..=4)),
+            thousands: rng.gen_bool(0.5),
+            blank_leading_zeros: rng.gen_bool(0.5),
+            align: if rng.gen_bool(0.5) {
+                Align::Left
+            } else {
+                Align::Right
+            },
+        };
+        let msg = format.format(num, tubes);
+        let context = format!("{num} on {tubes} tubes with {format:?}: {msg} (seed {seed})");
+        assert_eq!(msg.digits.len(), tubes, "{context}");
+        assert_eq!(msg.dots.len(), tubes, "{context}");
+
+        let value = match format.thousands {
+            true => num / Decimal::ONE_THOUSAND,
+            false => num,
+        };
+        if msg.digits.iter().all(|&d| d == OVERFLOW) {
+            // only numbers too long even without decimals overflow
+            let width = tubes - format.thousands as usize;
+            assert!(value.round_dp(0).to_string().len() > width, "{context}");
+            continue;
+        }
+
+        // reading the tubes back gives the number, rounded to the decimals shown; the "k" point
+        // sits on an unlit tube, the decimal point on a digit
+        let mut read = String::new();
+        let mut decimals = 0;
+        for (&digit, &dot) in msg.digits.iter().zip(&msg.dots) {
+            if digit.is_ascii_digit() {
+                if dot {
+                    read.push('.');
+                }
+                read.push(char::from(digit));
+                decimals += read.contains('.') as u32;
+            }
+        }
+        let marked = msg
+            .digits
+            .iter()
+            .zip(&msg.dots)
+            .filter(|(&digit, &dot)| dot && digit == b' ');
+        assert_eq!(marked.count(), format.thousands as usize, "{context}");
+        let read: Decimal = format!("0{read}").parse().expect(&context);
+        let unit = Decimal::new(1, decimals);
+        assert!((read - value).abs() * Decimal::TWO <= unit, "{context}");
+        if let Some(fixed) = format.decimals {
+            assert!(decimals <= fixed, "{context}");
+        }
+        match format.align {
+            Align::Left => assert!(msg.digits[0] != b' ', "{context}"),
+            Align::Right if format.blank_leading_zeros => {
+                assert!(msg.digits[0] != b'0' || read < Decimal::ONE, "{context}")
+            }
+            Align::Right => {}
+        }
+    }
+}
@@ -0,0 +1,696 @@
+use chrono::{NaiveDateTime, NaiveTime, Timelike};
+use rust_decimal::prelude::*;
+use serde::Deserialize;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use super::{
+    feed::Frame,
+    metrics::METRICS,
+    price_queue::Direction,
+    screen::{palette::Palette, Rgb888},
+    sink::DisplaySink,
+};
+
+mod format;
+mod protocol;
+#[cfg(feature = "nixie")]
+mod serial;
+
+pub use format::{Align, NumberFormat, OVERFLOW};
+pub use protocol::{Dots, Noritake, Protocol, ProtocolKind, Timd};
+
+/// What the tubes show, whatever the board
+#[derive(Debug, Clone)]
+pub struct NixieMsg {
+    text: String, // as shown
+    /// A digit, or a space for an unlit tube, per tube
+    pub digits: Vec<u8>,
+    /// Per tube, whether the decimal point left of its digit is lit
+    pub dots: Vec<bool>,
+}
+
+impl std::fmt::Display for NixieMsg {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+impl NixieMsg {
+    fn new(text: String, digits: &str, dots: Vec<bool>) -> Self {
+        NixieMsg {
+            text,
+            digits: digits.as_bytes().to_vec(),
+            dots,
+        }
+    }
+
+    /// `num` over `tubes` digits, with as many decimals as fit
+    pub fn from_decimal(num: Decimal, tubes: usize) -> Self {
+        NumberFormat::default().format(num, tubes)
+    }
+
+    pub fn flip_first_decimal_point(&mut self) {
+        self.dots[0] = !self.dots[0];
+    }
+
+    /// `HHMMSS` centered on `tubes`, or `HHMM` on fewer than 6, the decimal points left of the
+    /// minutes and seconds standing for colons
+    pub fn from_time(time: NaiveTime, tubes: usize) -> Self {
+        let (format, text) = if tubes >= 6 {
+            ("%H%M%S", "%H:%M:%S")
+        } else {
+            ("%H%M", "%H:%M")
+        };
+        let digits = time.format(format).to_string();
+        let left = tubes.saturating_sub(digits.len()) / 2;
+        let mut dots = vec![false; tubes];
+        for colon in (2..digits.len()).step_by(2) {
+            dots[left + colon] = true;
+        }
+        NixieMsg::new(
+            time.format(text).to_string(),
+            &format!("{:left$}{digits:<width$}", "", width = tubes - left),
+            dots,
+        )
+    }
+}
+
+/// What the tubes show
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// The price only
+    #[default]
+    Price,
+    /// The price for a while, then the time for a while, and over again
+    Alternate,
+    /// The time, but for the price shown at the start of every minute
+    Clock,
+}
+
+/// What the first decimal point tells of the prices
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Heartbeat {
+    /// Blink on every new price
+    #[default]
+    Blink,
+    /// Lit while the price goes up, unlit while it goes down
+    Direction,
+    /// Never lit
+    None,
+}
+
+/// When the tubes show the time rather than the price.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Schedule {
+    pub mode: Mode,
+    /// How long the price is shown, in each cycle or at the start of each minute
+    pub price_for: Duration,
+    /// How long the time is shown in each cycle, when alternating
+    pub time_for: Duration,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            mode: Mode::Price,
+            price_for: Duration::from_secs(20),
+            time_for: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Schedule {
+    pub fn shows_time(&self, now: NaiveTime) -> bool {
+        let since = |seconds: u32| {
+            Duration::from_secs(seconds as u64) + Duration::from_nanos(now.nanosecond() as u64)
+        };
+        match self.mode {
+            Mode::Price => false,
+            Mode::Alternate => {
+                // cycles start at midnight, so that several displays alternate together
+                let cycle = (self.price_for + self.time_for).as_millis();
+                cycle > 0
+                    && since(now.num_seconds_from_midnight()).as_millis() % cycle
+                        >= self.price_for.as_millis()
+            }
+            Mode::Clock => since(now.second()) >= self.price_for,
+        }
+    }
+}
+
+impl From<Decimal> for NixieMsg {
+    /// Over the 6 tubes of the original board
+    fn from(num: Decimal) -> Self {
+        NixieMsg::from_decimal(num, 6)
+    }
+}
+
+pub fn decimal_to_bytes(num: Decimal) -> NixieMsg {
+    num.into()
+}
+
+/// How long the tubes pause after each message
+const SEND_PAUSE: Duration = Duration::from_millis(50);
+/// How long each step of the cathode cycle is shown
+const CYCLE_STEP: Duration = Duration::from_millis(100);
+
+/// Runs every tube through all its digits now and then, as slot machines do. A cathode left unlit
+/// for weeks, e.g. all but the "9" of a leading digit, gets poisoned and stops glowing evenly.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CathodeCycle {
+    /// Cycle at this interval
+    #[serde(deserialize_with = "crate::config::deserialize_optional_duration")]
+    pub every: Option<Duration>,
+    /// Cycle every day at this time, as "HH:MM"
+    #[serde(deserialize_with = "crate::config::deserialize_optional_time_of_day")]
+    pub at: Option<NaiveTime>,
+    /// How long a cycle lasts
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub duration: Duration,
+}
+
+impl Default for CathodeCycle {
+    fn default() -> Self {
+        CathodeCycle {
+            every: None,
+            at: None,
+            duration: Duration::from_secs(5),
+        }
+    }
+}
+
+impl CathodeCycle {
+    /// Whether a cycle is due at `now`, the last one having run at `last`
+    pub fn is_due(&self, last: NaiveDateTime, now: NaiveDateTime) -> bool {
+        let interval_passed = self
+            .every
+            .is_some_and(|every| (now - last).to_std().is_ok_and(|elapsed| elapsed >= every));
+        let time_passed = self.at.is_some_and(|at| {
+            let today = now.date().and_time(at);
+            let latest = if today <= now {
+                today
+            } else {
+                today - chrono::Duration::days(1)
+            };
+            latest > last
+        });
+        interval_passed || time_passed
+    }
+
+    /// The messages of a cycle, one per [`CYCLE_STEP`]: each tube counts up from its own digit,
+    /// so that they all run through 0 to 9 every 10 steps.
+    pub fn messages(&self, tubes: usize) -> Vec<NixieMsg> {
+        let steps = (self.duration.as_millis() / CYCLE_STEP.as_millis()).max(10) as usize;
+        (0..steps)
+            .map(|step| {
+                let digits: String = (0..tubes)
+                    .map(|tube| char::from(b'0' + ((step + tube) % 10) as u8))
+                    .collect();
+                NixieMsg::new(digits.clone(), &digits, vec![false; tubes])
+            })
+            .collect()
+    }
+}
+
+#[derive(PartialEq)]
+enum Shown {
+    Price(Decimal),
+    Time(NaiveTime), // to the second
+}
+
+/// How often a lost serial port is looked for
+const REOPEN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Where the tubes are written to: a serial port, or e.g. a buffer in tests
+pub type Port = Box<dyn Write + Send>;
+
+/// Looks for a lost port by its name, returning the name it was found under
+type Reopen = Box<dyn FnMut(&str) -> anyhow::Result<(String, Port)> + Send>;
+
+pub struct Nixie {
+    out: Option<Port>, // none while the adapter is unplugged
+    port: String,
+    reopen: Option<Reopen>,
+    protocol: Box<dyn Protocol>,
+    format: NumberFormat,
+    last_open: Instant,     // last attempt at opening the port
+    brightness: Option<u8>, // restored on reopening
+    schedule: Schedule,
+    cathode_cycle: CathodeCycle,
+    last_cycle: NaiveDateTime,
+    shown: Option<Shown>,
+    heartbeat: Heartbeat,
+    dot_lit: bool,
+    underlight: Option<Palette>, // the up, down and flat colors
+    lit: Option<Rgb888>,         // the underlight color, once set
+    last_price: Option<Decimal>, // to tell where the price goes
+}
+
+impl Nixie {
+    /// Write to `out` instead of a serial port, named `name` in logs. Failing writes are only
+    /// reported.
+    pub fn with_port(name: String, out: impl Write + Send + 'static) -> Self {
+        Nixie {
+            out: Some(Box::new(out)),
+            port: name,
+            reopen: None,
+            protocol: Box::new(Timd::default()),
+            format: NumberFormat::default(),
+            last_open: Instant::now(),
+            brightness: None,
+            schedule: Schedule::default(),
+            cathode_cycle: CathodeCycle::default(),
+            last_cycle: chrono::Local::now().naive_local(),
+            shown: None,
+            heartbeat: Heartbeat::default(),
+            dot_lit: false,
+            underlight: None,
+            lit: None,
+            last_price: None,
+        }
+    }
+
+    pub fn with_protocol(mut self, protocol: Box<dyn Protocol>) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    pub fn with_number_format(mut self, format: NumberFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    pub fn with_cathode_cycle(mut self, cathode_cycle: CathodeCycle) -> Self {
+        self.cathode_cycle = cathode_cycle;
+        self
+    }
+
+    pub fn with_heartbeat(mut self, heartbeat: Heartbeat) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    /// Light the LEDs under the tubes in the `up`, `down` or `flat` color of `palette`, by where
+    /// the last price went
+    pub fn with_underlight(mut self, palette: Palette) -> Self {
+        self.underlight = Some(palette);
+        self
+    }
+    /// Write to the port, counting the failures. A failing serial port is taken for unplugged,
+    /// and looked for again on the next writes.
+    fn write(&mut self, bytes: &[u8]) -> anyhow::Result<()> {
+        self.reopen()?;
+        let out = self.out.as_mut().expect("reopened");
+        if let Err(e) = out.write_all(bytes).and_then(|()| out.flush()) {
+            METRICS.nixie_write_error();
+            if self.reopen.is_some() {
+                self.out = None;
+                self.last_open = Instant::now();
+            }
+            return Err(anyhow::Error::new(e).context(format!("Failed to write to {}", self.port)));
+        }
+        Ok(())
+    }
+    /// Reopen the port if it was lost, at most every [`REOPEN_INTERVAL`], then restore the
+    /// brightness
+    fn reopen(&mut self) -> anyhow::Result<()> {
+        if self.out.is_some() {
+            return Ok(());
+        }
+        let gone = format!(
+            "{} is gone, retrying every {}",
+            self.port,
+            humantime::format_duration(REOPEN_INTERVAL)
+        );
+        anyhow::ensure!(self.last_open.elapsed() >= REOPEN_INTERVAL, gone);
+        self.last_open = Instant::now();
+        let reopen = self.reopen.as_mut().expect("only ports reopened are lost");
+        let Ok((port, out)) = reopen(&self.port) else {
+            anyhow::bail!(gone);
+        };
+        log::info!("Reopened Nixie on {port}");
+        self.out = Some(out);
+        self.port = port;
+        self.shown = None;
+        self.lit = None;
+        if let Some(b) = self.brightness {
+            self.set_brightness(b)?;
+        }
+        Ok(())
+    }
+    pub async fn send(&mut self, msg: NixieMsg) -> anyhow::Result<()> {
+        self.write(&self.protocol.show(&msg))?;
+        tokio::time::sleep(SEND_PAUSE).await;
+        log::debug!("Sent to Nixie: {msg}");
+        Ok(())
+    }
+    async fn cycle_cathodes(&mut self) -> anyhow::Result<()> {
+        log::info!("Cycling the digits of every tube against cathode poisoning");
+        self.shown = None; // show the price or the time again right away
+        for msg in self.cathode_cycle.messages(self.protocol.digits()) {
+            self.send(msg).await?;
+            tokio::time::sleep(CYCLE_STEP.saturating_sub(SEND_PAUSE)).await;
+        }
+        Ok(())
+    }
+    /// From 0 to 8
+    pub fn set_brightness(&mut self, b: u8) -> anyhow::Result<()> {
+        anyhow::ensure!(b <= 8, "brightness should be between 0 and 8, got {b}");
+        self.brightness = Some(b);
+        self.write(&self.protocol.brightness(b))?;
+        log::info!("Set Nixie brightness to {b}");
+        Ok(())
+    }
+    /// Set the underlight to `color`, if the board has one and it's another color
+    fn set_underlight(&mut self, color: Rgb888) -> anyhow::Result<()> {
+        if self.lit == Some(color) {
+            return Ok(());
+        }
+        if let Some(bytes) = self.protocol.underlight(color) {
+            self.write(&bytes)?;
+            self.lit = Some(color);
+        }
+        Ok(())
+    }
+    pub fn close(&mut self) -> anyhow::Result<()> {
+        self.write(&self.protocol.blank())?;
+        if self.underlight.is_some() {
+            self.set_underlight(Rgb888::new(0, 0, 0))?;
+        }
+        log::info!("Closed Nixie");
+        Ok(())
+    }
+}
+
+impl DisplaySink for Nixie {
+    fn name(&self) -> String {
+        format!("Nixie on {}", self.port)
+    }
+
+    async fn render(&mut self, frame: &Frame) -> anyhow::Result<()> {
+        let now = chrono::Local::now().naive_local();
+        if self.cathode_cycle.is_due(self.last_cycle, now) {
+            self.last_cycle = now; // not retried until the next one if it fails
+            self.cycle_cathodes().await?;
+        }
+        let now = now.time().with_nanosecond(0).unwrap();
+        // the time is shown until a price comes in
+        if (frame.price.is_none() && self.schedule.mode != Mode::Price)
+            || self.schedule.shows_time(now)
+        {
+            // frames come in at least every second, for the matrix clock
+            if self.shown != Some(Shown::Time(now)) {
+                self.send(NixieMsg::from_time(now, self.protocol.digits()))
+                    .await?;
+                self.shown = Some(Shown::Time(now));
+            }
+            return Ok(());
+        }
+        let Some(price) = &frame.price else {
+            return Ok(());
+        };
+        // the tubes hold the price by themselves, only send it when it's new
+        if !frame.new_price && self.shown == Some(Shown::Price(price.price)) {
+            return Ok(());
+        }
+        let direction = self.last_price.map_or(Direction::Flat, |last| {
+            Direction::between(last, price.price)
+        });
+        if let Some(palette) = self.underlight {
+            self.set_underlight(match direction {
+                Direction::Up => palette.up,
+                Direction::Down => palette.down,
+                Direction::Flat => palette.flat,
+            })?;
+        }
+        let mut msg = self.format.format(price.price, self.protocol.digits());
+        self.dot_lit = match (self.heartbeat, direction) {
+            (Heartbeat::Blink, _) => !self.dot_lit,
+            (Heartbeat::Direction, Direction::Up) => true,
+            (Heartbeat::Direction, Direction::Down) => false,
+            (Heartbeat::Direction, Direction::Flat) => self.dot_lit,
+            (Heartbeat::None, _) => false,
+        };
+        if self.dot_lit {
+            msg.flip_first_decimal_point()
+        };
+        self.send(msg).await?;
+        self.shown = Some(Shown::Price(price.price));
+        self.last_price = Some(price.price);
+        Ok(())
+    }
+
+    async fn set_brightness(&mut self, brightness: u8) -> anyhow::Result<()> {
+        // the tubes have 9 levels
+        Nixie::set_brightness(self, ((brightness as u16 * 8 + 127) / 255) as u8)
+    }
+
+    async fn clear(&mut self) -> anyhow::Result<()> {
+        self.shown = None;
+        self.close()
+    }
+
+    async fn shutdown(&mut self) -> anyhow::Result<()> {
+        // don't leave a stale price lit on the tubes
+        self.clear().await
+    }
+}
+
+#[test]
+fn test_float_to_bytes() {
+    use rust_decimal_macros::dec;
+
+    let bytes = |num| Timd::default().show(&NixieMsg::from(num));
+    assert_eq!(bytes(dec!(100.2)), b"TIMD100200BBBLBB");
+    assert_eq!(bytes(dec!(0.1513)), b"TIMD151300LBBBBB");
+    assert_eq!(bytes(dec!(13568.0)), b"TIMD135680BBBBBL");
+    assert_eq!(bytes(dec!(141.51165)), b"TIMD141512BBBLBB");
+    assert_eq!(bytes(dec!(94395.23)), b"TIMD943952BBBBBL");
+    assert_eq!(bytes(dec!(124395.52)), b"TIMD124396BBBBBB");
+    assert_eq!(bytes(dec!(99999.73)), b"TIMD999997BBBBBL");
+    assert_eq!(bytes(dec!(100000)), b"TIMD100000BBBBBB");
+    assert_eq!(bytes(dec!(999999.5)), b"TIMD999999BBBBBB");
+}
+
+#[test]
+fn test_time_to_bytes() {
+    let time = NaiveTime::from_hms_opt(9, 5, 42).unwrap();
+    let msg = NixieMsg::from_time(time, 6);
+    assert_eq!(Timd::default().show(&msg), b"TIMD090542BBLBLB");
+    assert_eq!(msg.to_string(), "09:05:42");
+    let msg = NixieMsg::from_time(time, 4);
+    let four = Timd {
+        tubes: 4,
+        ..Timd::default()
+    };
+    assert_eq!(four.show(&msg), b"TIMD0905BBLB");
+    assert_eq!(msg.to_string(), "09:05");
+}
+
+#[test]
+fn test_schedule() {
+    let at = |h, m, s| NaiveTime::from_hms_opt(h, m, s).unwrap();
+    let schedule = |mode| Schedule {
+        mode,
+        ..Schedule::default()
+    };
+
+    assert!(!schedule(Mode::Price).shows_time(at(12, 0, 59)));
+
+    // 25s cycles from midnight: the price for 20s, then the time for 5s
+    let alternate = schedule(Mode::Alternate);
+    let shown: Vec<bool> = [0, 19, 20, 24, 25, 45]
+        .into_iter()
+        .map(|s| alternate.shows_time(at(0, 0, 0) + chrono::Duration::seconds(s)))
+        .collect();
+    assert_eq!(shown, [false, false, true, true, false, true]);
+    let never = Schedule {
+        time_for: Duration::ZERO,
+        ..alternate
+    };
+    assert!(!never.shows_time(at(0, 0, 24)));
+
+    let clock = schedule(Mode::Clock);
+    assert!(!clock.shows_time(at(8, 30, 0)));
+    assert!(!clock.shows_time(at(8, 30, 19)));
+    assert!(clock.shows_time(at(8, 30, 20)));
+    assert!(clock.shows_time(at(8, 30, 59)));
+}
+
+#[test]
+fn test_cathode_cycle() {
+    let at = |h, m| {
+        chrono::NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    };
+    let cycle = CathodeCycle {
+        every: Some(Duration::from_secs(3600)),
+        at: None,
+        duration: Duration::from_secs(2),
+    };
+    assert!(!cycle.is_due(at(8, 0), at(8, 59)));
+    assert!(cycle.is_due(at(8, 0), at(9, 0)));
+    assert!(!CathodeCycle::default().is_due(at(8, 0), at(23, 0)));
+
+    let nightly = CathodeCycle {
+        every: None,
+        at: NaiveTime::from_hms_opt(3, 0, 0),
+        ..cycle
+    };
+    assert!(!nightly.is_due(at(8, 0), at(23, 59)));
+    assert!(nightly.is_due(at(2, 59), at(3, 0)));
+    assert!(!nightly.is_due(at(3, 0), at(3, 1)));
+    // from the day before
+    assert!(nightly.is_due(at(0, 0) - chrono::Duration::hours(22), at(2, 0)));
+
+    // every tube goes through every digit, twice in 2s
+    let messages = cycle.messages(6);
+    assert_eq!(messages.len(), 20);
+    assert_eq!(messages[0].digits, b"012345");
+    assert_eq!(messages[1].digits, b"123456");
+    assert_eq!(messages[9].digits, b"901234");
+    for tube in 0..6 {
+        let mut digits: Vec<u8> = messages[..10].iter().map(|m| m.digits[tube]).collect();
+        digits.sort();
+        assert_eq!(digits, b"0123456789");
+    }
+    assert_eq!(messages[10].digits, messages[0].digits);
+    assert_eq!(Timd::default().show(&messages[0]), b"TIMD012345BBBBBB");
+    assert_eq!(cycle.messages(8)[0].digits, b"01234567");
+}
+
+/// Collects what's written to the tubes, failing while `broken`
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct Wire {
+    bytes: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+    broken: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+#[cfg(test)]
+impl Wire {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.bytes.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+impl Write for Wire {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.broken.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(std::io::ErrorKind::BrokenPipe.into());
+        }
+        self.bytes.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_byte_stream() {
+    use super::{screen::Screen, ws_coin::ConnectionState, ws_coin::Price};
+    use rust_decimal_macros::dec;
+
+    let wire = Wire::default();
+    let mut nixie = Nixie::with_port("wire".to_string(), wire.clone());
+    nixie.set_brightness(5).unwrap();
+    nixie.send(dec!(94395.23).into()).await.unwrap();
+    nixie.close().unwrap();
+    assert_eq!(wire.take(), b"TIMB5TIMD943952BBBBBLTIMDBBBBBBBBBBBB");
+    assert!(nixie.set_brightness(9).is_err());
+    assert!(wire.take().is_empty());
+
+    let frame = |price, new_price| Frame {
+        screen: Screen::default(),
+        price: Some(Price {
+            symbol: "BTCUSDT".to_string(),
+            name: "BTC".to_string(),
+            price,
+            change_24h: None,
+        }),
+        new_price,
+        connection: ConnectionState::Connected,
+    };
+    // the first decimal point blinks on every new price, and a price shown isn't sent again
+    nixie.render(&frame(dec!(100.2), true)).await.unwrap();
+    nixie.render(&frame(dec!(100.2), false)).await.unwrap();
+    nixie.render(&frame(dec!(100.2), true)).await.unwrap();
+    DisplaySink::set_brightness(&mut nixie, 128).await.unwrap();
+    nixie.shutdown().await.unwrap();
+    assert_eq!(
+        wire.take(),
+        [
+            &b"TIMD100200LBBLBB"[..],
+            b"TIMD100200BBBLBB",
+            b"TIMB4",
+            b"TIMDBBBBBBBBBBBB"
+        ]
+        .concat()
+    );
+
+    // the price is sent again once the port works again
+    wire.broken
+        .store(true, std::sync::atomic::Ordering::Relaxed);
+    assert!(nixie.render(&frame(dec!(100.2), false)).await.is_err());
+    wire.broken
+        .store(false, std::sync::atomic::Ordering::Relaxed);
+    nixie.render(&frame(dec!(100.2), false)).await.unwrap();
+    assert_eq!(wire.take(), b"TIMD100200BBBLBB");
+}
+
+#[tokio::test]
+async fn test_direction() {
+    use super::{screen::Screen, ws_coin::ConnectionState, ws_coin::Price};
+    use rust_decimal_macros::dec;
+
+    let wire = Wire::default();
+    let mut nixie = Nixie::with_port("wire".to_string(), wire.clone())
+        .with_heartbeat(Heartbeat::Direction)
+        .with_underlight(Palette::default());
+    for price in [dec!(100.2), dec!(100.3), dec!(100.3), dec!(100.1)] {
+        let frame = Frame {
+            screen: Screen::default(),
+            price: Some(Price {
+                symbol: "BTCUSDT".to_string(),
+                name: "BTC".to_string(),
+                price,
+                change_24h: None,
+            }),
+            new_price: true,
+            connection: ConnectionState::Connected,
+        };
+        nixie.render(&frame).await.unwrap();
+    }
+    nixie.shutdown().await.unwrap();
+    // blue for the first price, then the first point lit along with green while going up, and
+    // unlit along with red going down
+    assert_eq!(
+        wire.take(),
+        [
+            &b"TIMC0000CC"[..],
+            b"TIMD100200BBBLBB",
+            b"TIMC00CC00",
+            b"TIMD100300LBBLBB",
+            b"TIMC0000CC",
+            b"TIMD100300LBBLBB",
+            b"TIMCCC0000",
+            b"TIMD100100BBBLBB",
+            b"TIMDBBBBBBBBBBBB",
+            b"TIMC000000",
+        ]
+        .concat()
+    );
+}
@@ -0,0 +1,180 @@
+use super::{NixieMsg, OVERFLOW};
+use crate::screen::Rgb888;
+
+/// How a board is told what to show
+pub trait Protocol: Send {
+    /// How many digits the board shows
+    fn digits(&self) -> usize;
+    /// Show `msg`, made of [`Protocol::digits`] digits
+    fn show(&self, msg: &NixieMsg) -> Vec<u8>;
+    /// Turn every digit off
+    fn blank(&self) -> Vec<u8>;
+    /// From 0 to 8
+    fn brightness(&self, level: u8) -> Vec<u8>;
+    /// Light the LEDs under the tubes in `color`, black for off, if the board has them
+    fn underlight(&self, _color: Rgb888) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// The boards known, by the name given in the config file
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProtocolKind {
+    /// The `TIMD` tube boards, with 6 or 8 tubes
+    #[default]
+    Timd,
+    /// A Noritake CU-series VFD, or another character display taking its command set
+    Noritake,
+}
+
+/// Where the decimal point of a tube sits
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dots {
+    /// Left of the digit, as on the IN-14
+    #[default]
+    Left,
+    /// Right of the digit, as on the IN-12B
+    Right,
+    /// No decimal points at all
+    None,
+}
+
+impl ProtocolKind {
+    pub fn protocol(self, digits: usize, dots: Dots) -> Box<dyn Protocol> {
+        match self {
+            ProtocolKind::Timd => Box::new(Timd {
+                tubes: digits,
+                dots,
+            }),
+            ProtocolKind::Noritake => Box::new(Noritake {
+                width: digits,
+                dots: dots != Dots::None,
+            }),
+        }
+    }
+}
+
+/// `TIMD`, then a digit or `B` for an unlit tube per tube, then `L` or `B` per tube to light its
+/// decimal point or not. `TIMB` and a digit sets the brightness, `TIMC` and `RRGGBB` in hex the
+/// color of the LEDs under the tubes. Having no minus, the tubes show [`OVERFLOW`] as all 9s,
+/// whether the decimal points are wired or not.
+pub struct Timd {
+    pub tubes: usize,
+    pub dots: Dots,
+}
+
+impl Default for Timd {
+    fn default() -> Self {
+        Timd {
+            tubes: 6,
+            dots: Dots::Left,
+        }
+    }
+}
+
+impl Protocol for Timd {
+    fn digits(&self) -> usize {
+        self.tubes
+    }
+
+    fn show(&self, msg: &NixieMsg) -> Vec<u8> {
+        let digits = msg.digits.iter().map(|&d| match d {
+            OVERFLOW => b'9',
+            d if d.is_ascii_digit() => d,
+            _ => b'B',
+        });
+        // a point left of a digit is the one right of the digit before
+        let lit = |tube: usize| match self.dots {
+            Dots::None => false,
+            Dots::Left => msg.dots[tube],
+            Dots::Right => msg.dots.get(tube + 1).copied().unwrap_or(false),
+        };
+        let dots = (0..msg.dots.len()).map(|tube| if lit(tube) { b'L' } else { b'B' });
+        b"TIMD".iter().copied().chain(digits).chain(dots).collect()
+    }
+
+    fn blank(&self) -> Vec<u8> {
+        [b"TIMD".to_vec(), vec![b'B'; self.tubes * 2]].concat()
+    }
+
+    fn brightness(&self, level: u8) -> Vec<u8> {
+        format!("TIMB{level}").into_bytes()
+    }
+
+    fn underlight(&self, color: Rgb888) -> Option<Vec<u8>> {
+        let [r, g, b] = color.to_array();
+        Some(format!("TIMC{r:02X}{g:02X}{b:02X}").into_bytes())
+    }
+}
+
+/// Noritake CU-series command set: the display cleared, then the digits written as text with the
+/// decimal points taking a character of their own. Its 8 brightness levels start at 12.5%, so 0
+/// is as dim as 1; the VFD has to be set to 9600 bauds.
+pub struct Noritake {
+    pub width: usize,
+    pub dots: bool,
+}
+
+/// Clear the display and bring the cursor home
+const CLEAR: u8 = 0x0c;
+/// Followed by 1 to 8, the brightness
+const BRIGHTNESS: [u8; 2] = [0x1f, 0x58];
+
+impl Protocol for Noritake {
+    fn digits(&self) -> usize {
+        self.width
+    }
+
+    fn show(&self, msg: &NixieMsg) -> Vec<u8> {
+        let mut bytes = vec![CLEAR];
+        for (&digit, &dot) in msg.digits.iter().zip(&msg.dots) {
+            if dot && self.dots {
+                bytes.push(b'.');
+            }
+            bytes.push(digit);
+        }
+        bytes
+    }
+
+    fn blank(&self) -> Vec<u8> {
+        vec![CLEAR]
+    }
+
+    fn brightness(&self, level: u8) -> Vec<u8> {
+        [&BRIGHTNESS[..], &[level.clamp(1, 8)]].concat()
+    }
+}
+
+#[test]
+fn test_protocols() {
+    use rust_decimal_macros::dec;
+
+    let msg = NixieMsg::from_decimal(dec!(94395.23), 8);
+    let timd = |dots| Timd { tubes: 8, dots };
+    assert_eq!(timd(Dots::Left).show(&msg), b"TIMD94395230BBBBBLBB");
+    assert_eq!(timd(Dots::Right).show(&msg), b"TIMD94395230BBBBLBBB");
+    assert_eq!(timd(Dots::None).show(&msg), b"TIMD94395230BBBBBBBB");
+    let overflow = NixieMsg::from_decimal(dec!(123456789), 8);
+    for dots in [Dots::Left, Dots::Right, Dots::None] {
+        assert_eq!(timd(dots).show(&overflow), b"TIMD99999999BBBBBBBB");
+    }
+    assert_eq!(timd(Dots::Left).blank(), b"TIMDBBBBBBBBBBBBBBBB");
+    assert_eq!(
+        timd(Dots::Left).underlight(Rgb888::new(0, 204, 0)),
+        Some(b"TIMC00CC00".to_vec())
+    );
+
+    let vfd = Noritake {
+        width: 8,
+        dots: true,
+    };
+    assert_eq!(vfd.show(&msg), b"\x0c94395.230");
+    assert_eq!(vfd.show(&overflow), b"\x0c--------");
+    let time = NixieMsg::from_time(chrono::NaiveTime::from_hms_opt(9, 5, 42).unwrap(), 8);
+    assert_eq!(vfd.show(&time), b"\x0c 09.05.42 ");
+    assert_eq!(vfd.brightness(0), [0x1f, 0x58, 1]);
+    assert_eq!(vfd.brightness(8), [0x1f, 0x58, 8]);
+    assert_eq!(vfd.underlight(Rgb888::new(0, 204, 0)), None);
+}
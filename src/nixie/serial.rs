@@ -0,0 +1,144 @@
+use anyhow::Context as _;
+use std::time::Duration;
+
+use serialport::{SerialPortInfo, SerialPortType, UsbPortInfo};
+
+use super::{Nixie, Port};
+
+fn open_port(port: &str) -> anyhow::Result<Port> {
+    let ser = serialport::new(port, 9600)
+        .timeout(Duration::from_millis(100))
+        .open()
+        .with_context(|| format!("Failed to open {port}"))?;
+    Ok(Box::new(ser))
+}
+
+/// The USB adapter `port` is on, if it's one
+fn adapter(port: &str) -> Option<UsbPortInfo> {
+    let info = serialport::available_ports()
+        .ok()?
+        .into_iter()
+        .find(|p| p.port_name == port)?;
+    match info.port_type {
+        SerialPortType::UsbPort(usb) => Some(usb),
+        _ => None,
+    }
+}
+
+/// Open a lost serial port, on the USB `adapter` it was opened on whatever its name now
+fn reopen_serial(port: &str, adapter: Option<&UsbPortInfo>) -> anyhow::Result<(String, Port)> {
+    let candidates = match serialport::available_ports() {
+        Ok(available) => reopen_candidates(port, adapter, &available),
+        Err(_) => vec![port.to_string()],
+    };
+    candidates
+        .into_iter()
+        .find_map(|port| open_port(&port).ok().map(|ser| (port, ser)))
+        .ok_or_else(|| anyhow::anyhow!("{port} is gone"))
+}
+
+impl Nixie {
+    /// Open the tubes on `serialport`, looking for it again if it's unplugged
+    pub fn new(serialport: String) -> anyhow::Result<Self> {
+        let ser = open_port(&serialport)?;
+        let adapter = adapter(&serialport);
+        let mut nixie = Self::with_port(serialport, ser);
+        nixie.reopen = Some(Box::new(move |port| reopen_serial(port, adapter.as_ref())));
+        Ok(nixie)
+    }
+}
+
+/// The ports to look for a lost `port` on: those of the same USB `adapter`, by its vendor,
+/// product and serial number, as plugged back in it may come back as e.g. /dev/ttyUSB1. Another
+/// device may have taken the name meanwhile, so `port` alone if the adapter isn't known.
+fn reopen_candidates(
+    port: &str,
+    adapter: Option<&UsbPortInfo>,
+    available: &[SerialPortInfo],
+) -> Vec<String> {
+    let Some(adapter) = adapter else {
+        return vec![port.to_string()];
+    };
+    let key = |usb: &UsbPortInfo| (usb.vid, usb.pid, usb.serial_number.clone());
+    let on_adapter = |p: &&SerialPortInfo| match &p.port_type {
+        SerialPortType::UsbPort(usb) => key(usb) == key(adapter),
+        _ => false,
+    };
+    let mut ports: Vec<String> = available
+        .iter()
+        .filter(on_adapter)
+        .map(|p| p.port_name.clone())
+        .collect();
+    ports.sort_by_key(|p| p != port); // stable, its own name first
+    ports
+}
+
+#[test]
+fn test_reopen_candidates() {
+    let usb = |pid, serial: &str| UsbPortInfo {
+        vid: 0x1a86,
+        pid,
+        serial_number: Some(serial.to_string()),
+        manufacturer: None,
+        product: None,
+    };
+    let port = |name: &str, port_type| SerialPortInfo {
+        port_name: name.to_string(),
+        port_type,
+    };
+    let nixie = usb(0x7523, "A1");
+    let available = [
+        port("/dev/ttyUSB0", SerialPortType::UsbPort(usb(0x7523, "Z9"))), // e.g. a Zigbee stick
+        port("/dev/ttyS0", SerialPortType::PciPort),
+        port("/dev/ttyUSB1", SerialPortType::UsbPort(nixie.clone())),
+    ];
+    assert_eq!(
+        reopen_candidates("/dev/ttyUSB0", Some(&nixie), &available),
+        ["/dev/ttyUSB1"]
+    );
+    assert!(reopen_candidates("/dev/ttyUSB0", Some(&nixie), &available[..2]).is_empty());
+    // not on a USB adapter, only looked for by its name
+    assert_eq!(
+        reopen_candidates("/dev/ttyS0", None, &available),
+        ["/dev/ttyS0"]
+    );
+}
+
+#[test]
+fn list_serial_port() {
+    let ports = serialport::available_ports().expect("No ports found!");
+    for p in ports {
+        log::info!("{}", p.port_name);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs the tubes on /dev/ttyUSB0"]
+async fn test_nixie() {
+    use super::decimal_to_bytes;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use std::thread::sleep;
+
+    let mut nixie = Nixie::new("/dev/ttyUSB0".to_owned()).unwrap();
+    nixie.set_brightness(8).unwrap();
+    for p in 0..=9 {
+        nixie
+            .send(decimal_to_bytes(Decimal::from(p) * dec!(11111.1)))
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(200));
+    }
+
+    (0..=8)
+        .rev()
+        .map(|b| {
+            nixie.set_brightness(b).unwrap();
+            sleep(Duration::from_millis(200));
+        })
+        .for_each(drop);
+    assert!(nixie.set_brightness(9).is_err());
+
+    nixie.close().unwrap();
+    sleep(Duration::from_millis(200));
+}
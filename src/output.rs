@@ -0,0 +1,115 @@
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use super::{awtrix::Protocol, screen::term_style::TermStyle};
+
+/// A device to show the feed on, as given on the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Output {
+    /// `terminal[=<term-style>]`
+    Terminal(TermStyle),
+    /// `awtrix=<host>[:<port>]` for an Awtrix 2 host, `awtrix3=<host>[:<port>]` for Awtrix 3
+    /// firmware
+    Awtrix {
+        host: String,
+        port: u16,
+        protocol: Protocol,
+    },
+    /// `web[=<ip>:<port>]`
+    Web(SocketAddr),
+    /// `nixie[=<serial-port>]`
+    #[cfg(feature = "nixie")]
+    Nixie { serial_port: String },
+}
+
+impl<'de> serde::Deserialize<'de> for Output {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Split `host[:port]`, falling back to `default_port`
+fn host_port(s: &str, default_port: u16) -> Result<(String, u16), String> {
+    match s.rsplit_once(':') {
+        Some((host, port)) => Ok((
+            host.to_string(),
+            port.parse().map_err(|_| format!("invalid port {port:?}"))?,
+        )),
+        None => Ok((s.to_string(), default_port)),
+    }
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, target) = match s.split_once('=') {
+            Some((kind, target)) => (kind, Some(target)),
+            None => (s, None),
+        };
+        let awtrix = |protocol, default_port| {
+            let target = target.ok_or(format!("{kind} needs a host, e.g. {kind}=192.168.1.50"))?;
+            let (host, port) = host_port(target, default_port)?;
+            Ok(Output::Awtrix {
+                host,
+                port,
+                protocol,
+            })
+        };
+        match kind {
+            "terminal" => Ok(Output::Terminal(
+                target.unwrap_or("auto").parse::<TermStyle>()?,
+            )),
+            "awtrix" => awtrix(Protocol::V2, 7000),
+            "awtrix3" => awtrix(Protocol::V3, 80),
+            "web" => Ok(Output::Web(
+                target
+                    .unwrap_or("127.0.0.1:8080")
+                    .parse()
+                    .map_err(|e| format!("invalid address: {e}"))?,
+            )),
+            #[cfg(feature = "nixie")]
+            "nixie" => Ok(Output::Nixie {
+                serial_port: target.unwrap_or("/dev/ttyUSB0").to_string(),
+            }),
+            #[cfg(not(feature = "nixie"))]
+            "nixie" => Err("nixie support is not compiled in, build with --features nixie".into()),
+            _ => Err(format!(
+                "unknown output {kind:?}, expected terminal, awtrix, awtrix3, web or nixie"
+            )),
+        }
+    }
+}
+
+#[test]
+fn test_parse_output() {
+    assert_eq!(
+        "terminal".parse::<Output>(),
+        Ok(Output::Terminal(TermStyle::detect()))
+    );
+    assert_eq!(
+        "awtrix3=192.168.1.50".parse::<Output>(),
+        Ok(Output::Awtrix {
+            host: "192.168.1.50".to_string(),
+            port: 80,
+            protocol: Protocol::V3,
+        })
+    );
+    assert_eq!(
+        "awtrix=localhost:7001".parse::<Output>(),
+        Ok(Output::Awtrix {
+            host: "localhost".to_string(),
+            port: 7001,
+            protocol: Protocol::V2,
+        })
+    );
+    assert_eq!(
+        "web".parse::<Output>(),
+        Ok(Output::Web(([127, 0, 0, 1], 8080).into()))
+    );
+    assert!("awtrix".parse::<Output>().is_err());
+    assert!("awtrix=host:port".parse::<Output>().is_err());
+    assert!("lcd=/dev/fb0".parse::<Output>().is_err());
+}
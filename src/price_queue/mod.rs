@@ -3,17 +3,29 @@ use std::collections::vec_deque::VecDeque;
 use std::fmt;
 
 use rust_decimal::prelude::*;
-use rust_decimal_macros::dec;
 
-use super::screen::{Rgb888, Screen};
+use super::screen::{palette::Palette, Rgb888, Screen};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Direction {
     Flat,
     Up,
     Down,
 }
 
+impl Direction {
+    /// Where the price went from `from` to `to`
+    pub fn between(from: Decimal, to: Decimal) -> Self {
+        match to.cmp(&from) {
+            Ordering::Equal => Direction::Flat,
+            Ordering::Greater => Direction::Up,
+            Ordering::Less => Direction::Down,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum PlotKind {
     TrendLine,
     FlatLine,
@@ -22,23 +34,44 @@ pub enum PlotKind {
 #[derive(Debug)]
 pub struct PriceQueue {
     q: VecDeque<Decimal>,
+    capacity: usize,
 }
 
 impl Default for PriceQueue {
+    /// The last 32 prices, as wide as the Awtrix matrix
     fn default() -> Self {
-        PriceQueue {
-            q: VecDeque::with_capacity(32),
-        }
+        PriceQueue::with_capacity(32)
     }
 }
 
 impl PriceQueue {
+    /// The last `capacity` prices, the first one repeated until there are as many
+    pub fn with_capacity(capacity: usize) -> Self {
+        PriceQueue {
+            q: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Keep the last `capacity` prices instead, repeating the oldest one if there are fewer
+    pub fn resize(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.q.len() > capacity {
+            self.q.pop_front();
+        }
+        if let Some(&oldest) = self.q.front() {
+            while self.q.len() < capacity {
+                self.q.push_front(oldest);
+            }
+        }
+    }
+
     pub fn push(&mut self, p: Decimal) {
-        if self.q.len() == 32 {
+        if self.q.len() == self.capacity {
             self.q.pop_front();
         }
         self.q.push_back(p);
-        while self.q.len() < 32 {
+        while self.q.len() < self.capacity {
             self.q.push_back(p);
         }
     }
@@ -46,16 +79,7 @@ impl PriceQueue {
     pub fn to_up_down(&self) -> Vec<Direction> {
         let mut v = vec![Direction::Flat];
         for i in 1..self.q.len() {
-            let p = self.q.get(i);
-            let pre_p = self.q.get(i - 1);
-
-            let d: Direction = match p.cmp(&pre_p) {
-                Ordering::Equal => Direction::Flat,
-                Ordering::Greater => Direction::Up,
-                Ordering::Less => Direction::Down,
-            };
-
-            v.push(d);
+            v.push(Direction::between(self.q[i - 1], self.q[i]));
         }
         v
     }
@@ -72,33 +96,49 @@ impl PriceQueue {
             .collect()
     }
 
-    pub fn to_int_pos_v(&self) -> Vec<usize> {
-        let (min, max) = (self.q.iter().min().unwrap(), self.q.iter().max().unwrap());
+    /// The last `width` prices, or all of them if there are fewer, and where each went
+    fn last(&self, width: usize) -> Vec<(Decimal, Direction)> {
+        let skip = self.q.len().saturating_sub(width);
+        self.q
+            .iter()
+            .copied()
+            .zip(self.to_up_down())
+            .skip(skip)
+            .collect()
+    }
+
+    /// The row of each of the last `width` prices, from 0 at the bottom to `height - 1`
+    pub fn to_int_pos_v(&self, width: usize, height: usize) -> Vec<usize> {
+        let prices: Vec<Decimal> = self.last(width).into_iter().map(|(p, _)| p).collect();
+        let (Some(min), Some(max)) = (prices.iter().min(), prices.iter().max()) else {
+            return Vec::new(); // no price yet
+        };
         let rng: Decimal = max - min;
+        let top = Decimal::from(height - 1);
         if rng.is_zero() {
-            vec![3; 32]
+            vec![(height - 1) / 2; prices.len()]
         } else {
-            self.q
+            prices
                 .iter()
-                .map(|p| ((p - min) / rng * dec!(7.0)).round().to_f64().unwrap() as usize)
+                .map(|p| ((p - min) / rng * top).round().to_f64().unwrap() as usize)
                 .collect()
         }
     }
 
-    pub fn to_2d_array(&self) -> Vec<Vec<bool>> {
-        let mut array = vec![vec![false; 32]; 8];
+    pub fn to_2d_array(&self, width: usize, height: usize) -> Vec<Vec<bool>> {
+        let mut array = vec![vec![false; self.q.len().min(width)]; height];
 
-        for (col, i) in self.to_int_pos_v().iter().enumerate() {
-            array[7 - i][col] = true;
+        for (col, i) in self.to_int_pos_v(width, height).iter().enumerate() {
+            array[height - 1 - i][col] = true;
         }
 
         array
     }
 
-    fn to_2d_direction_array(&self) -> Vec<Vec<Option<Direction>>> {
-        let up_down = self.to_up_down();
+    fn to_2d_direction_array(&self, width: usize, height: usize) -> Vec<Vec<Option<Direction>>> {
+        let up_down: Vec<Direction> = self.last(width).into_iter().map(|(_, d)| d).collect();
 
-        self.to_2d_array()
+        self.to_2d_array(width, height)
             .iter()
             .map(|row| {
                 row.iter()
@@ -113,19 +153,34 @@ impl PriceQueue {
     }
 
     pub fn to_screen(&self, plot_kind: PlotKind, show_num: bool) -> Screen {
-        let dim = 0.8;
-        let dim_max = (255.0 * dim) as u8;
-        let screen = match plot_kind {
+        let screen = self.to_colored_screen(plot_kind, &Palette::default(), 32, 8);
+        if show_num {
+            screen + Screen::from_float(self.q[self.q.len() - 1])
+        } else {
+            screen
+        }
+    }
+
+    /// The chart of the last `width` prices over `height` rows, drawn with the up, down and flat
+    /// colors of `palette`
+    pub fn to_colored_screen(
+        &self,
+        plot_kind: PlotKind,
+        palette: &Palette,
+        width: usize,
+        height: usize,
+    ) -> Screen {
+        match plot_kind {
             PlotKind::TrendLine => Screen {
                 pixels: self
-                    .to_2d_direction_array()
+                    .to_2d_direction_array(width, height)
                     .iter()
                     .map(|row| {
                         row.iter()
                             .map(|x| match x {
-                                Some(Direction::Flat) => Some(Rgb888::new(0, 0, dim_max)),
-                                Some(Direction::Up) => Some(Rgb888::new(0, dim_max, 0)),
-                                Some(Direction::Down) => Some(Rgb888::new(dim_max, 0, 0)),
+                                Some(Direction::Flat) => Some(palette.flat),
+                                Some(Direction::Up) => Some(palette.up),
+                                Some(Direction::Down) => Some(palette.down),
                                 _ => None,
                             })
                             .collect()
@@ -133,27 +188,22 @@ impl PriceQueue {
                     .collect::<Vec<Vec<Option<Rgb888>>>>(),
             },
             PlotKind::FlatLine => {
-                let mut screen = Screen::default();
+                let mut screen = Screen::new(width, height);
                 screen.draw(
                     &[self
-                        .to_up_down()
+                        .last(width)
                         .iter()
-                        .map(|d| match d {
-                            Direction::Flat => Some(Rgb888::new(0, 0, dim_max)),
-                            Direction::Up => Some(Rgb888::new(0, dim_max, 0)),
-                            Direction::Down => Some(Rgb888::new(dim_max, 0, 0)),
+                        .map(|(_, d)| match d {
+                            Direction::Flat => Some(palette.flat),
+                            Direction::Up => Some(palette.up),
+                            Direction::Down => Some(palette.down),
                         })
                         .collect()],
                     0,
-                    4,
+                    height / 2,
                 );
                 screen
             }
-        };
-        if show_num {
-            screen + Screen::from_float(self.q[31])
-        } else {
-            screen
         }
     }
 }
@@ -163,3 +213,31 @@ impl fmt::Display for PriceQueue {
         write!(f, "{:}", self.to_screen(PlotKind::TrendLine, true))
     }
 }
+
+#[test]
+fn test_chart_size() {
+    let mut pq = PriceQueue::with_capacity(4);
+    for p in [1, 2, 3, 2, 4] {
+        pq.push(Decimal::from(p));
+    }
+    let lit = |screen: Screen| -> Vec<String> {
+        screen
+            .pixels
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|p| if p.is_some() { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    };
+    // the last 3 of 2, 3, 2, 4 over 5 rows, and all 4 of them however wide
+    let chart = pq.to_colored_screen(PlotKind::TrendLine, &Palette::default(), 3, 5);
+    assert_eq!(lit(chart), ["..#", "...", "#..", "...", ".#."]);
+    let chart = pq.to_colored_screen(PlotKind::FlatLine, &Palette::default(), 6, 3);
+    assert_eq!(lit(chart), ["......", "####..", "......"]);
+
+    pq.resize(6);
+    assert_eq!(pq.to_up_down().len(), 6);
+    assert_eq!(pq.to_int_pos_v(6, 3), [0, 0, 0, 1, 0, 2]); // the oldest repeated
+}
@@ -0,0 +1,245 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::watch;
+
+use super::{brightness::BrightnessSchedule, config::Config, feed::FeedSettings};
+
+/// How often the config file is checked for changes. Polling rather than file system events, as
+/// those are unreliable on the bind mounts of containers.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Applies the changes of the config file to the running feed and outputs, when it's saved or on
+/// SIGHUP.
+///
+/// Markets, layout, colors, alerts, the nixie brightness and the brightness schedule apply live.
+/// The other settings are read when opening the outputs, and only apply on restart.
+pub struct Reloader {
+    path: Option<PathBuf>,
+    load: Box<dyn FnMut() -> anyhow::Result<Config> + Send>,
+    running: Config,
+    feed: FeedSettings,
+    nixie_brightness: watch::Sender<Option<u8>>,
+    brightness: watch::Sender<BrightnessSchedule>,
+}
+
+impl Reloader {
+    /// Watch `path`, reading it with `load` so that the command line flags keep taking precedence.
+    /// Without a config file, there's nothing to reload.
+    pub fn new(
+        path: Option<PathBuf>,
+        load: impl FnMut() -> anyhow::Result<Config> + Send + 'static,
+        running: Config,
+        feed: FeedSettings,
+    ) -> Self {
+        Reloader {
+            path,
+            load: Box::new(load),
+            nixie_brightness: watch::Sender::new(Some(running.nixie.dimmer_level())),
+            brightness: watch::Sender::new(running.brightness.clone()),
+            running,
+            feed,
+        }
+    }
+
+    /// Brightness of the nixie tubes, see [`Drivers::add_dimmed`]
+    ///
+    /// [`Drivers::add_dimmed`]: crate::sink::Drivers::add_dimmed
+    pub fn nixie_brightness(&self) -> watch::Sender<Option<u8>> {
+        self.nixie_brightness.clone()
+    }
+
+    /// The brightness schedule, see [`BrightnessSchedule::run`]
+    pub fn brightness_schedule(&self) -> watch::Receiver<BrightnessSchedule> {
+        self.brightness.subscribe()
+    }
+
+    pub async fn run(mut self) {
+        let Some(path) = self.path.clone() else {
+            return std::future::pending().await;
+        };
+        let modified = || std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let mut last_modified: Option<SystemTime> = modified();
+        #[cfg(unix)]
+        let mut hangups = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .expect("Failed to listen to SIGHUP");
+        let mut poll = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            #[cfg(unix)]
+            let hangup = hangups.recv();
+            #[cfg(not(unix))]
+            let hangup = std::future::pending::<Option<()>>();
+            tokio::select! {
+                _ = hangup => log::info!("Received SIGHUP, reloading {}", path.display()),
+                _ = poll.tick() => {
+                    let now_modified = modified();
+                    if now_modified == last_modified {
+                        continue;
+                    }
+                    last_modified = now_modified;
+                    log::info!("{} changed, reloading", path.display());
+                }
+            }
+            match (self.load)() {
+                Ok(config) => self.apply(config),
+                // e.g. saved halfway, the next save will be picked up
+                Err(e) => log::error!("Failed to reload, keeping the running config: {e:#}"),
+            }
+        }
+    }
+
+    fn apply(&mut self, config: Config) {
+        let running = &self.running;
+        let mut applied = Vec::new();
+        if config.markets != running.markets {
+            self.feed.set_markets(config.markets.clone());
+            applied.push("markets");
+        }
+        if config.layout != running.layout {
+            self.feed.set_layout(config.layout.clone());
+            applied.push("layout");
+        }
+        if config.colors != running.colors {
+            self.feed.set_palette(config.colors);
+            applied.push("colors");
+        }
+        if config.alerts != running.alerts {
+            self.feed.set_alerts(config.alerts.clone());
+            applied.push("alerts");
+        }
+        if config.nixie.brightness != running.nixie.brightness {
+            self.nixie_brightness
+                .send_replace(Some(config.nixie.dimmer_level()));
+            applied.push("nixie.brightness");
+        }
+        if config.brightness != running.brightness {
+            self.brightness.send_replace(config.brightness.clone());
+            applied.push("brightness");
+        }
+        if applied.is_empty() {
+            log::info!("Nothing to apply live");
+        } else {
+            log::info!("Applied the new {}", applied.join(", "));
+        }
+        for key in self.restart_needed(&config) {
+            log::warn!("`{key}` changed, restart to apply it");
+        }
+
+        // the other settings stay as the outputs were opened with, until a restart
+        let running = &mut self.running;
+        running.markets = config.markets;
+        running.layout = config.layout;
+        running.colors = config.colors;
+        running.alerts = config.alerts;
+        running.nixie.brightness = config.nixie.brightness;
+        running.brightness = config.brightness;
+    }
+
+    /// The settings of `config` differing from those running, that only apply on restart
+    fn restart_needed(&self, config: &Config) -> Vec<&'static str> {
+        let running = &self.running;
+        let restart = [
+            ("source", config.source != running.source),
+            ("terminal", config.terminal != running.terminal),
+            ("awtrix", config.awtrix != running.awtrix),
+            ("web", config.web != running.web),
+            ("mqtt", config.mqtt != running.mqtt),
+            (
+                "nixie.serial_port",
+                config.nixie.serial_port != running.nixie.serial_port,
+            ),
+            (
+                "nixie.protocol",
+                (config.nixie.protocol, config.nixie.tubes, config.nixie.dots)
+                    != (
+                        running.nixie.protocol,
+                        running.nixie.tubes,
+                        running.nixie.dots,
+                    ),
+            ),
+            ("nixie.format", config.nixie.format != running.nixie.format),
+            (
+                "nixie.heartbeat",
+                config.nixie.heartbeat != running.nixie.heartbeat,
+            ),
+            (
+                "nixie.underlight",
+                config.nixie.underlight != running.nixie.underlight,
+            ),
+            (
+                "nixie.mode",
+                config.nixie.schedule() != running.nixie.schedule(),
+            ),
+            (
+                "nixie.cathode_cycle",
+                config.nixie.cathode_cycle != running.nixie.cathode_cycle,
+            ),
+            ("run", config.run != running.run),
+            ("control", config.control != running.control),
+            ("metrics", config.metrics != running.metrics),
+        ];
+        restart
+            .into_iter()
+            .filter_map(|(key, changed)| changed.then_some(key))
+            .collect()
+    }
+}
+
+#[test]
+fn test_reload() {
+    let mut reloader = Reloader::new(
+        None,
+        || unreachable!(),
+        Config::default(),
+        FeedSettings::detached(Config::default().markets),
+    );
+    let mut edited = Config::default();
+    edited.source.reconnect_delay = Duration::from_secs(1);
+    edited.nixie.brightness = 4;
+    reloader.apply(edited.clone());
+    assert_eq!(
+        *reloader.nixie_brightness().borrow(),
+        Some(edited.nixie.dimmer_level())
+    );
+    // still to restart for on the next reload, and nothing to restart for once reverted
+    assert_eq!(reloader.restart_needed(&edited), ["source"]);
+    reloader.apply(Config::default());
+    assert!(reloader.restart_needed(&Config::default()).is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_reload_brightness() {
+    use super::{
+        brightness::{Level, Period},
+        sink::Dimmer,
+    };
+
+    let mut reloader = Reloader::new(
+        None,
+        || unreachable!(),
+        Config::default(),
+        FeedSettings::detached(Config::default().markets),
+    );
+    let dimmer = Dimmer::default();
+    tokio::spawn(BrightnessSchedule::run(
+        reloader.brightness_schedule(),
+        dimmer.clone(),
+    ));
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert_eq!(dimmer.level(), None); // no schedule yet
+
+    let edited = Config {
+        brightness: BrightnessSchedule {
+            ramp: Duration::ZERO,
+            schedule: vec![Period {
+                from: chrono::NaiveTime::MIN,
+                level: Level::On(10),
+            }],
+        },
+        ..Config::default()
+    };
+    reloader.apply(edited.clone());
+    assert!(reloader.restart_needed(&edited).is_empty());
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(dimmer.level(), Some(10));
+}
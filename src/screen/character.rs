@@ -5,7 +5,8 @@ use super::rgb::Rgb888;
 use rust_decimal::prelude::*;
 
 // Medium and Small fonts inspied by https://github.com/oidoid/mem
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Font {
     Large,
     Medium,
@@ -21,6 +22,13 @@ impl Character {
     pub fn new(c: char, font: Font) -> Character {
         let o = Some(Rgb888::new(255, 255, 255));
         let x = None;
+        if let Some(rows) = glyph(c, font) {
+            let pixels = rows
+                .iter()
+                .map(|row| row.chars().map(|p| if p == '#' { o } else { x }).collect())
+                .collect();
+            return Character { font, pixels };
+        }
         match font {
             Font::Large => match c {
                 ' ' => Character {
@@ -375,28 +383,26 @@ impl Character {
             },
         }
     }
-    pub fn from_float(p: Decimal, font: Font) -> Self {
-        let p = {
-            match font {
-                Font::Medium if format!("{:.2}", p).len() > 8 => format!("{:.1}", p), // bitcoin price exceeds 100k
-                _ => format!("{:.2}", p),
-            }
-        };
-        p.chars()
+    pub fn from_string(s: &str, font: Font) -> Self {
+        s.chars()
             .map(|c| Character::new(c, font))
             .reduce(|a, b| a + b)
             .unwrap()
     }
+    pub fn float_to_string(p: Decimal, font: Font) -> String {
+        match font {
+            Font::Medium if format!("{:.2}", p).len() > 8 => format!("{:.1}", p), // bitcoin price exceeds 100k
+            _ => format!("{:.2}", p),
+        }
+    }
+    pub fn from_float(p: Decimal, font: Font) -> Self {
+        Character::from_string(&Character::float_to_string(p, font), font)
+    }
     pub fn from_time(font: Font) -> Self {
         use chrono::Local;
         let dt = Local::now();
 
-        dt.format("%H:%M:%S")
-            .to_string()
-            .chars()
-            .map(|c| Character::new(c, font))
-            .reduce(|a, b| a + b)
-            .unwrap()
+        Character::from_string(&dt.format("%H:%M:%S").to_string(), font)
     }
 }
 
@@ -406,6 +412,50 @@ impl std::fmt::Display for Character {
     }
 }
 
+/// Letters of the large font, case insensitive, and signs of every font, for tickers and
+/// percentages
+fn glyph(c: char, font: Font) -> Option<&'static [&'static str]> {
+    Some(match (font, c.to_ascii_uppercase()) {
+        (Font::Medium, '+') => &["...", ".#.", "###", ".#."],
+        (Font::Medium, '-') => &["...", "...", "###", "..."],
+        (Font::Medium, '%') => &["#.#", "..#", "#..", "#.#"],
+        (Font::Small, '+') => &[".#.", "###", ".#."],
+        (Font::Small, '-') => &["...", "###", "..."],
+        (Font::Small, '%') => &["#.#", ".#.", "#.#"],
+        (Font::Medium | Font::Small, _) => return None,
+        (Font::Large, 'A') => &[".#.", "#.#", "###", "#.#", "#.#"],
+        (Font::Large, 'B') => &["##.", "#.#", "##.", "#.#", "##."],
+        (Font::Large, 'C') => &[".##", "#..", "#..", "#..", ".##"],
+        (Font::Large, 'D') => &["##.", "#.#", "#.#", "#.#", "##."],
+        (Font::Large, 'E') => &["###", "#..", "##.", "#..", "###"],
+        (Font::Large, 'F') => &["###", "#..", "##.", "#..", "#.."],
+        (Font::Large, 'G') => &[".##", "#..", "#.#", "#.#", ".##"],
+        (Font::Large, 'H') => &["#.#", "#.#", "###", "#.#", "#.#"],
+        (Font::Large, 'I') => &["###", ".#.", ".#.", ".#.", "###"],
+        (Font::Large, 'J') => &["..#", "..#", "..#", "#.#", ".#."],
+        (Font::Large, 'K') => &["#.#", "#.#", "##.", "#.#", "#.#"],
+        (Font::Large, 'L') => &["#..", "#..", "#..", "#..", "###"],
+        (Font::Large, 'M') => &["#...#", "##.##", "#.#.#", "#...#", "#...#"],
+        (Font::Large, 'N') => &["#..#", "##.#", "#.##", "#..#", "#..#"],
+        (Font::Large, 'O') => &[".#.", "#.#", "#.#", "#.#", ".#."],
+        (Font::Large, 'P') => &["##.", "#.#", "##.", "#..", "#.."],
+        (Font::Large, 'Q') => &[".#.", "#.#", "#.#", "##.", ".##"],
+        (Font::Large, 'R') => &["##.", "#.#", "##.", "#.#", "#.#"],
+        (Font::Large, 'S') => &[".##", "#..", ".#.", "..#", "##."],
+        (Font::Large, 'T') => &["###", ".#.", ".#.", ".#.", ".#."],
+        (Font::Large, 'U') => &["#.#", "#.#", "#.#", "#.#", "###"],
+        (Font::Large, 'V') => &["#.#", "#.#", "#.#", "#.#", ".#."],
+        (Font::Large, 'W') => &["#...#", "#...#", "#.#.#", "##.##", "#...#"],
+        (Font::Large, 'X') => &["#.#", "#.#", ".#.", "#.#", "#.#"],
+        (Font::Large, 'Y') => &["#.#", "#.#", ".#.", ".#.", ".#."],
+        (Font::Large, 'Z') => &["###", "..#", ".#.", "#..", "###"],
+        (Font::Large, '+') => &["...", ".#.", "###", ".#.", "..."],
+        (Font::Large, '-') => &["...", "...", "###", "...", "..."],
+        (Font::Large, '%') => &["#.#", "..#", ".#.", "#..", "#.#"],
+        _ => return None,
+    })
+}
+
 fn concat_horizontal_of_2_vecs<T: Clone>(v1: Vec<Vec<T>>, v2: Vec<Vec<T>>) -> Vec<Vec<T>> {
     assert_eq!(v1.len(), v2.len());
 
@@ -0,0 +1,261 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use super::{Rgb888, Screen};
+use crate::{feed::Frame, sink::DisplaySink};
+
+const GAP: Rgb888 = Rgb888::new(0, 0, 0);
+const LED_OFF: Rgb888 = Rgb888::new(24, 24, 24);
+
+impl Screen {
+    /// Render as an RGB image where every pixel becomes a round LED of `scale` x `scale` image
+    /// pixels, with dark gaps in between. Returns `(width, height, rgb_data)`.
+    pub fn to_rgb_image(&self, scale: usize) -> (usize, usize, Vec<u8>) {
+        let (rows, cols) = (self.pixels.len(), self.pixels[0].len());
+        let (width, height) = (cols * scale, rows * scale);
+
+        // LED radius leaves a gap of roughly a tenth of the pitch between neighbours
+        let center = (scale as f64 - 1.0) / 2.0;
+        let radius = scale as f64 * 0.45;
+        let in_dot: Vec<bool> = (0..scale * scale)
+            .map(|i| {
+                let (dx, dy) = ((i % scale) as f64 - center, (i / scale) as f64 - center);
+                dx * dx + dy * dy <= radius * radius
+            })
+            .collect();
+
+        let mut data = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                let color = if in_dot[(y % scale) * scale + x % scale] {
+                    self.pixels[y / scale][x / scale].unwrap_or(LED_OFF)
+                } else {
+                    GAP
+                };
+                data.extend_from_slice(&color.to_array());
+            }
+        }
+        (width, height, data)
+    }
+
+    pub fn to_png(&self, scale: usize) -> Vec<u8> {
+        let (width, height, data) = self.to_rgb_image(scale);
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().expect("valid png header");
+        writer.write_image_data(&data).expect("valid png data");
+        writer.finish().expect("png written to memory");
+        png
+    }
+}
+
+/// Build an exact palette for a frame, which is possible as long as it has at most 256 colors.
+fn to_indexed(rgb: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut indices = Vec::with_capacity(rgb.len() / 3);
+    for color in rgb.chunks_exact(3) {
+        let color = [color[0], color[1], color[2]];
+        let i = match palette.iter().position(|c| *c == color) {
+            Some(i) => i,
+            None => {
+                palette.push(color);
+                palette.len() - 1
+            }
+        };
+        indices.push(u8::try_from(i).ok()?);
+    }
+    Some((indices, palette.concat()))
+}
+
+/// Records a stream of `Screen`s into an animated GIF, keeping the time each one was on display.
+pub struct GifRecorder<W: Write> {
+    writer: Option<W>,
+    encoder: Option<gif::Encoder<W>>,
+    scale: usize,
+    pending: Option<gif::Frame<'static>>, // last frame, waiting to know its delay
+    written: u64,                         // animation length written so far, in centiseconds
+}
+
+impl<W: Write> GifRecorder<W> {
+    pub fn new(writer: W, scale: usize) -> Self {
+        GifRecorder {
+            writer: Some(writer),
+            encoder: None,
+            scale,
+            pending: None,
+            written: 0,
+        }
+    }
+
+    /// Add `screen`, shown `at` since the start of the recording.
+    pub fn push(&mut self, screen: &Screen, at: Duration) -> anyhow::Result<()> {
+        let (width, height, rgb) = screen.to_rgb_image(self.scale);
+        let (width, height) = (u16::try_from(width)?, u16::try_from(height)?);
+        if let Some(writer) = self.writer.take() {
+            let mut encoder = gif::Encoder::new(writer, width, height, &[])?;
+            encoder.set_repeat(gif::Repeat::Infinite)?;
+            self.encoder = Some(encoder);
+        }
+        let frame = match to_indexed(&rgb) {
+            Some((indices, palette)) => {
+                gif::Frame::from_palette_pixels(width, height, indices, palette, None)
+            }
+            None => gif::Frame::from_rgb_speed(width, height, &rgb, 10),
+        };
+        self.flush_pending(at)?;
+        self.pending = Some(frame);
+        Ok(())
+    }
+
+    /// Write the last frame, shown until `at`, and return the underlying writer.
+    pub fn finish(mut self, at: Duration) -> anyhow::Result<W> {
+        self.flush_pending(at)?;
+        match (self.encoder, self.writer) {
+            (Some(encoder), _) => Ok(encoder.into_inner()?),
+            (None, Some(_)) => anyhow::bail!("no frame was recorded"),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    fn flush_pending(&mut self, until: Duration) -> anyhow::Result<()> {
+        let Some(mut frame) = self.pending.take() else {
+            return Ok(());
+        };
+        // derive delays from absolute timestamps so rounding errors don't accumulate
+        let until = (until.as_millis() / 10) as u64;
+        frame.delay = u16::try_from(until.saturating_sub(self.written)).unwrap_or(u16::MAX);
+        self.written += frame.delay as u64;
+        self.encoder.as_mut().unwrap().write_frame(&frame)?;
+        Ok(())
+    }
+}
+
+/// Records the frames rendered to it into a GIF file, written on shutdown.
+pub struct GifFile {
+    path: PathBuf,
+    recorder: Option<GifRecorder<File>>,
+    started: Instant,
+    size: (usize, usize), // of the matrix, in LEDs
+}
+
+impl GifFile {
+    /// Record to `path` the frames of a `width` x `height` matrix, each LED `scale` pixels wide
+    pub fn create(
+        path: PathBuf,
+        scale: usize,
+        (width, height): (usize, usize),
+    ) -> anyhow::Result<Self> {
+        let size = (width * scale, height * scale);
+        anyhow::ensure!(
+            size.0.max(size.1) <= u16::MAX as usize,
+            "A scale of {scale} makes a {}x{} GIF, over the {} pixels a side GIFs allow",
+            size.0,
+            size.1,
+            u16::MAX
+        );
+        let file = File::create(&path)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {e}", path.display()))?;
+        Ok(GifFile {
+            recorder: Some(GifRecorder::new(file, scale)),
+            path,
+            started: Instant::now(),
+            size: (width, height),
+        })
+    }
+}
+
+impl DisplaySink for GifFile {
+    fn name(&self) -> String {
+        format!("recording to {}", self.path.display())
+    }
+
+    async fn render(&mut self, frame: &Frame) -> anyhow::Result<()> {
+        let at = self.started.elapsed();
+        match self.recorder.as_mut() {
+            Some(recorder) => recorder.push(&frame.screen, at),
+            None => anyhow::bail!("the recording is finished"),
+        }
+    }
+
+    async fn clear(&mut self) -> anyhow::Result<()> {
+        let at = self.started.elapsed();
+        match self.recorder.as_mut() {
+            Some(recorder) => {
+                let (width, height) = self.size;
+                recorder.push(&Screen::new(width, height), at)
+            }
+            None => anyhow::bail!("the recording is finished"),
+        }
+    }
+
+    async fn shutdown(&mut self) -> anyhow::Result<()> {
+        let Some(recorder) = self.recorder.take() else {
+            return Ok(());
+        };
+        recorder.finish(self.started.elapsed())?;
+        log::info!(
+            "Recorded {:?} to {}",
+            self.started.elapsed(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
+
+#[test]
+fn test_to_png() {
+    let mut screen = Screen::default();
+    screen.draw(&[vec![Some(Rgb888::new(255, 0, 0))]], 1, 0);
+
+    let png = screen.to_png(10);
+    let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).unwrap();
+    assert_eq!((info.width, info.height), (320, 80));
+
+    let at = |x: usize, y: usize| &data[(y * 320 + x) * 3..(y * 320 + x) * 3 + 3];
+    assert_eq!(at(15, 5), [255, 0, 0]); // center of the lit LED
+    assert_eq!(at(5, 5), LED_OFF.to_array()); // center of an unlit LED
+    assert_eq!(at(10, 0), GAP.to_array()); // corner between LEDs
+}
+
+#[test]
+fn test_gif_recorder() {
+    let mut recorder = GifRecorder::new(Vec::new(), 4);
+    recorder.push(&Screen::default(), Duration::ZERO).unwrap();
+    recorder
+        .push(
+            &Screen::from_float(rust_decimal_macros::dec!(1.5)),
+            Duration::from_millis(1234),
+        )
+        .unwrap();
+    let gif = recorder.finish(Duration::from_millis(2000)).unwrap();
+
+    let mut decoder = gif::DecodeOptions::new().read_info(gif.as_slice()).unwrap();
+    assert_eq!((decoder.width(), decoder.height()), (128, 32));
+    let mut delays = vec![];
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        delays.push(frame.delay);
+    }
+    assert_eq!(delays, [123, 77]);
+}
+
+#[test]
+fn test_gif_file_errors() {
+    let path = std::env::temp_dir().join("cyberpunk_display_test.gif");
+    let err = GifFile::create(path, 300, (256, 8)).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "A scale of 300 makes a 76800x2400 GIF, over the 65535 pixels a side GIFs allow"
+    );
+    let missing = PathBuf::from("/nonexistent/matrix.gif");
+    assert!(GifFile::create(missing, 10, (32, 8))
+        .err()
+        .unwrap()
+        .to_string()
+        .starts_with("Failed to create /nonexistent/matrix.gif: "));
+}
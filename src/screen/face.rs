@@ -0,0 +1,323 @@
+use serde::Deserialize;
+
+use super::{palette::Palette, Rgb888, Screen};
+use crate::price_queue::Direction;
+
+/// What a region of a face shows
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Widget {
+    /// The price of the market shown, in `price_font`
+    Price,
+    /// The time, in `time_font` and `time_format`
+    Clock,
+    /// The price chart, as `plot` says
+    Chart,
+    /// The name of the market shown
+    Ticker,
+    /// A small picture, see [`Icon`]
+    Icon,
+    /// The change over 24h, e.g. "+1.2%", in `time_font`
+    Change,
+    /// A dot blinking on each new price, steady while disconnected
+    Status,
+}
+
+/// The pictures of [`Widget::Icon`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Icon {
+    #[default]
+    Bitcoin,
+    /// An arrow up or down, or a dash, as the last price went
+    Trend,
+}
+
+impl Icon {
+    /// The picture, the trend being where the last price went
+    pub fn pixels(self, trend: Direction, palette: &Palette) -> Vec<Vec<Option<Rgb888>>> {
+        let (art, color): (&[&str], _) = match (self, trend) {
+            (Icon::Bitcoin, _) => (
+                &[
+                    ".#.#.", "####.", ".#..#", ".###.", ".#..#", "####.", ".#.#.",
+                ],
+                Rgb888::new(247, 147, 26),
+            ),
+            (Icon::Trend, Direction::Up) => (&["..#..", ".###.", "#####"], palette.up),
+            (Icon::Trend, Direction::Down) => (&["#####", ".###.", "..#.."], palette.down),
+            (Icon::Trend, Direction::Flat) => (&["#####"], palette.flat),
+        };
+        art.iter()
+            .map(|row| row.chars().map(|p| (p == '#').then_some(color)).collect())
+            .collect()
+    }
+}
+
+/// Where a widget sits across its region
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Where a widget sits down its region
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VAlign {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// Pixels of the screen left out of a region, on each side
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Margin {
+    pub top: usize,
+    pub right: usize,
+    pub bottom: usize,
+    pub left: usize,
+}
+
+/// A widget, and the part of the screen it's drawn in
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Region {
+    pub widget: Widget,
+    #[serde(default)]
+    pub align: Align,
+    #[serde(default)]
+    pub valign: VAlign,
+    /// The region is the whole screen but for its margin
+    #[serde(default)]
+    pub margin: Margin,
+    /// Regions of a higher `z` are drawn over the others, and regions of the same `z` in order
+    #[serde(default)]
+    pub z: i32,
+    /// The picture of an icon
+    #[serde(default)]
+    pub icon: Icon,
+}
+
+impl Region {
+    /// `widget` over the whole screen, at the top left
+    pub fn new(widget: Widget) -> Self {
+        Region {
+            widget,
+            align: Align::default(),
+            valign: VAlign::default(),
+            margin: Margin::default(),
+            z: 0,
+            icon: Icon::default(),
+        }
+    }
+
+    pub fn with_align(mut self, align: Align, valign: VAlign) -> Self {
+        self.align = align;
+        self.valign = valign;
+        self
+    }
+
+    pub fn with_margin(mut self, top: usize, right: usize, bottom: usize, left: usize) -> Self {
+        self.margin = Margin {
+            top,
+            right,
+            bottom,
+            left,
+        };
+        self
+    }
+
+    pub fn with_z(mut self, z: i32) -> Self {
+        self.z = z;
+        self
+    }
+
+    pub fn with_icon(mut self, icon: Icon) -> Self {
+        self.icon = icon;
+        self
+    }
+
+    /// The region on a `width` x `height` screen, as its left, top, right and bottom edges
+    fn bounds(&self, width: usize, height: usize) -> (usize, usize, usize, usize) {
+        let m = &self.margin;
+        let (left, top) = (m.left.min(width), m.top.min(height));
+        let right = width.saturating_sub(m.right).max(left);
+        let bottom = height.saturating_sub(m.bottom).max(top);
+        (left, top, right, bottom)
+    }
+
+    /// The width and height of the region on a `width` x `height` screen
+    pub fn size(&self, width: usize, height: usize) -> (usize, usize) {
+        let (left, top, right, bottom) = self.bounds(width, height);
+        (right - left, bottom - top)
+    }
+
+    /// Where a widget of `size` goes on a `width` x `height` screen, its top left corner. A widget
+    /// larger than the region sticks to its top left, so that a long price shows its first digits.
+    pub fn place(&self, size: (usize, usize), width: usize, height: usize) -> (usize, usize) {
+        let (left, top, right, bottom) = self.bounds(width, height);
+        let offset = |room: usize, size: usize, share: usize| room.saturating_sub(size) * share / 2;
+        let x = match self.align {
+            Align::Left => 0,
+            Align::Center => 1,
+            Align::Right => 2,
+        };
+        let y = match self.valign {
+            VAlign::Top => 0,
+            VAlign::Middle => 1,
+            VAlign::Bottom => 2,
+        };
+        (
+            left + offset(right - left, size.0, x),
+            top + offset(bottom - top, size.1, y),
+        )
+    }
+}
+
+/// How the matrix is laid out: regions of widgets, for any screen size
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct Face {
+    pub regions: Vec<Region>,
+}
+
+impl Default for Face {
+    fn default() -> Self {
+        Face::classic()
+    }
+}
+
+impl Face {
+    /// The price at the top right over its chart, the time below it, and the status dot at the
+    /// bottom left
+    pub fn classic() -> Self {
+        Face {
+            regions: vec![
+                Region::new(Widget::Chart),
+                Region::new(Widget::Price)
+                    .with_align(Align::Right, VAlign::Top)
+                    .with_margin(0, 1, 0, 0)
+                    .with_z(1),
+                Region::new(Widget::Clock)
+                    .with_align(Align::Right, VAlign::Bottom)
+                    .with_margin(0, 1, 0, 0)
+                    .with_z(1),
+                Region::new(Widget::Status)
+                    .with_align(Align::Left, VAlign::Bottom)
+                    .with_z(2),
+            ],
+        }
+    }
+
+    /// A `width` x `height` screen with the widgets drawn by `draw`, lowest `z` first. A widget
+    /// is cut to its region, and hides what's under it; `draw` returns `None` for one with
+    /// nothing to show.
+    pub fn compose(
+        &self,
+        width: usize,
+        height: usize,
+        mut draw: impl FnMut(&Region) -> Option<Vec<Vec<Option<Rgb888>>>>,
+    ) -> Screen {
+        let mut screen = Screen::new(width, height);
+        let mut regions: Vec<&Region> = self.regions.iter().collect();
+        regions.sort_by_key(|r| r.z); // stable, keeping the order of the same z
+        for region in regions {
+            let Some(pixels) = draw(region) else {
+                continue;
+            };
+            let size = (pixels.first().map_or(0, |row| row.len()), pixels.len());
+            let (x0, y0) = region.place(size, width, height);
+            let (_, _, right, bottom) = region.bounds(width, height);
+            let cut: Vec<Vec<Option<Rgb888>>> = pixels
+                .iter()
+                .take(bottom.saturating_sub(y0))
+                .map(|row| row.iter().take(right.saturating_sub(x0)).copied().collect())
+                .collect();
+            if cut.first().is_some_and(|row| !row.is_empty()) {
+                screen.draw(&cut, x0, y0);
+            }
+        }
+        screen
+    }
+}
+
+#[test]
+fn test_face() {
+    let block = |w: usize, h: usize, c: u8| vec![vec![Some(Rgb888::new(c, c, c)); w]; h];
+    let lit = |screen: &Screen| -> Vec<String> {
+        screen
+            .pixels
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|p| match p.map(|p| p.to_array()[0]) {
+                        None => '.',
+                        Some(c) => char::from(b'0' + c),
+                    })
+                    .collect()
+            })
+            .collect()
+    };
+
+    // the classic face, as the matrix was laid out by hand
+    let screen = Face::classic().compose(12, 6, |region| match region.widget {
+        Widget::Chart => Some(block(12, 6, 1)),
+        Widget::Price => Some(block(5, 2, 2)),
+        Widget::Clock => Some(block(4, 1, 3)),
+        Widget::Status => Some(block(1, 1, 4)),
+        _ => None,
+    });
+    assert_eq!(
+        lit(&screen),
+        [
+            "111111222221",
+            "111111222221",
+            "111111111111",
+            "111111111111",
+            "111111111111",
+            "411111133331",
+        ]
+    );
+
+    // centered, cut to the region, and over the regions of lower z whatever their order
+    let face = Face {
+        regions: vec![
+            Region::new(Widget::Price)
+                .with_align(Align::Center, VAlign::Middle)
+                .with_margin(1, 2, 1, 2)
+                .with_z(1),
+            Region::new(Widget::Chart),
+            Region::new(Widget::Ticker).with_margin(0, 0, 0, 10),
+        ],
+    };
+    let screen = face.compose(12, 6, |region| match region.widget {
+        Widget::Chart => Some(block(12, 6, 1)),
+        Widget::Price => Some(block(4, 2, 2)),
+        Widget::Ticker => Some(block(5, 3, 3)),
+        _ => None,
+    });
+    assert_eq!(
+        lit(&screen),
+        [
+            "111111111133",
+            "111111111133",
+            "111122221133",
+            "111122221111",
+            "111111111111",
+            "111111111111",
+        ]
+    );
+
+    // too large for its region, from the top left
+    let region = Region::new(Widget::Price).with_align(Align::Right, VAlign::Bottom);
+    assert_eq!(region.place((40, 3), 32, 8), (0, 5));
+    assert_eq!(region.place((4, 3), 64, 16), (60, 13));
+    let region = region.with_margin(1, 2, 0, 3);
+    assert_eq!(region.size(32, 8), (27, 7));
+    assert_eq!(region.size(4, 8), (0, 7));
+}
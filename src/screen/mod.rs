@@ -1,25 +1,32 @@
 use std::ops;
 
 pub mod character;
+pub mod export;
+pub mod face;
+pub mod palette;
 mod pixels_to_string;
 pub mod rgb;
+pub mod term_style;
+pub mod theme;
+pub mod transition;
 
 use rust_decimal::prelude::*;
 
 use character::{Character, Font};
 
-use pixels_to_string::pixels_to_string;
+use pixels_to_string::{pixels_to_string, pixels_to_styled_string};
 pub use rgb::Rgb888;
+use term_style::TermStyle;
 
+#[derive(Clone)]
 pub struct Screen {
     pub pixels: Vec<Vec<Option<Rgb888>>>,
 }
 
 impl Default for Screen {
+    /// The 32x8 matrix of Awtrix
     fn default() -> Self {
-        Screen {
-            pixels: vec![vec![None; 32]; 8],
-        }
+        Screen::new(32, 8)
     }
 }
 
@@ -30,24 +37,40 @@ impl std::fmt::Display for Screen {
 }
 
 impl Screen {
-    fn _is_in_screen(x: usize, y: usize) -> bool {
-        (x <= 31) & (y <= 7)
+    /// A blank screen
+    pub fn new(width: usize, height: usize) -> Self {
+        Screen {
+            pixels: vec![vec![None; width]; height],
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.pixels.first().map_or(0, |row| row.len())
+    }
+    pub fn height(&self) -> usize {
+        self.pixels.len()
+    }
+    /// `(width, height)`
+    pub fn size(&self) -> (usize, usize) {
+        (self.width(), self.height())
+    }
+    fn _is_in_screen(&self, x: usize, y: usize) -> bool {
+        (x < self.width()) & (y < self.height())
     }
     pub fn draw(&mut self, pixels: &[Vec<Option<Rgb888>>], x0: usize, y0: usize) -> &Self {
         let height = pixels.len();
         let width = pixels[0].len();
 
         assert!(
-            Self::_is_in_screen(x0, y0),
+            self._is_in_screen(x0, y0),
             "Starting point ({}, {}) is out of screen",
             x0,
             y0
         );
 
+        #[allow(clippy::needless_range_loop)]
         for x in 0..width {
-            #[allow(clippy::needless_range_loop)]
             for y in 0..height {
-                if Self::_is_in_screen(x + x0, y + y0) {
+                if self._is_in_screen(x + x0, y + y0) {
                     self.pixels[y + y0][x + x0] = pixels[y][x];
                 }
             }
@@ -55,6 +78,9 @@ impl Screen {
 
         self
     }
+    pub fn to_term_string(&self, style: &TermStyle) -> String {
+        pixels_to_styled_string(&self.pixels, style)
+    }
     pub fn from_chars(cs: Character) -> Self {
         let mut screen = Screen::default();
         screen.draw(&cs.pixels, 32 - (cs.pixels[0].len() + 1), 0);
@@ -0,0 +1,89 @@
+use serde::Deserialize;
+
+use super::{
+    theme::{Theme, ThemeName},
+    Rgb888,
+};
+
+/// Colors of the matrix
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(from = "Colors")]
+pub struct Palette {
+    pub price: Rgb888,
+    pub time: Rgb888,
+    /// Price chart, where the price went up
+    pub up: Rgb888,
+    /// Price chart, where the price went down
+    pub down: Rgb888,
+    /// Price chart, where the price didn't move
+    pub flat: Rgb888,
+    /// The dot blinking at each new price
+    pub indicator: Rgb888,
+    /// The pixels left off; black leaves them off
+    pub background: Rgb888,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        ThemeName::default().theme().into()
+    }
+}
+
+impl From<Theme> for Palette {
+    fn from(theme: Theme) -> Self {
+        Palette {
+            price: theme.text,
+            time: theme.clock,
+            up: theme.up,
+            down: theme.down,
+            flat: theme.flat,
+            indicator: theme.accent,
+            background: theme.background,
+        }
+    }
+}
+
+/// `[colors]` as written: a theme, and the colors set apart from it
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Colors {
+    theme: ThemeName,
+    price: Option<Rgb888>,
+    time: Option<Rgb888>,
+    up: Option<Rgb888>,
+    down: Option<Rgb888>,
+    flat: Option<Rgb888>,
+    indicator: Option<Rgb888>,
+    background: Option<Rgb888>,
+}
+
+impl From<Colors> for Palette {
+    fn from(colors: Colors) -> Self {
+        let theme = Palette::from(colors.theme.theme());
+        Palette {
+            price: colors.price.unwrap_or(theme.price),
+            time: colors.time.unwrap_or(theme.time),
+            up: colors.up.unwrap_or(theme.up),
+            down: colors.down.unwrap_or(theme.down),
+            flat: colors.flat.unwrap_or(theme.flat),
+            indicator: colors.indicator.unwrap_or(theme.indicator),
+            background: colors.background.unwrap_or(theme.background),
+        }
+    }
+}
+
+#[test]
+fn test_palette() {
+    let palette: Palette = toml::from_str("theme = \"colorblind\"\nup = \"#00ff00\"").unwrap();
+    assert_eq!(palette.up, Rgb888::new(0, 255, 0));
+    assert_eq!(palette.down, ThemeName::Colorblind.theme().down);
+    assert_eq!(toml::from_str::<Palette>("").unwrap(), Palette::default());
+
+    // every theme tells the chart colors apart, and shows the text over its background
+    use clap::ValueEnum;
+    for name in ThemeName::value_variants() {
+        let theme = name.theme();
+        assert!(theme.up != theme.down && theme.down != theme.flat && theme.up != theme.flat);
+        assert!(theme.text != theme.background && theme.clock != theme.background);
+    }
+}
@@ -1,4 +1,5 @@
 pub use super::rgb::Rgb888;
+use super::term_style::{Colors, Glyphs, TermStyle};
 
 pub fn pixels_to_string(pixels: &[Vec<Option<Rgb888>>]) -> String {
     let (dot, blank) = ("██".to_string(), "  ".to_string());
@@ -17,3 +18,198 @@ pub fn pixels_to_string(pixels: &[Vec<Option<Rgb888>>]) -> String {
         .collect::<Vec<String>>()
         .join("\n")
 }
+
+pub fn pixels_to_styled_string(pixels: &[Vec<Option<Rgb888>>], style: &TermStyle) -> String {
+    match style.glyphs {
+        Glyphs::Block if style.colors == Colors::TrueColor => pixels_to_string(pixels),
+        Glyphs::Block => pixels
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|x| match x {
+                        Some(rgb888) => format!("\x1b[{}m██\x1b[0m", fg(*rgb888, style.colors)),
+                        _ => "  ".to_string(),
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+        Glyphs::HalfBlock => pixels
+            .chunks(2)
+            .map(|rows| {
+                let blank = vec![None; rows[0].len()];
+                let bottom = rows.get(1).unwrap_or(&blank);
+                rows[0]
+                    .iter()
+                    .zip(bottom)
+                    .map(|(top, bottom)| match (top, bottom) {
+                        (None, None) => " ".to_string(),
+                        (Some(top), None) => format!("\x1b[{}m▀\x1b[0m", fg(*top, style.colors)),
+                        (None, Some(bottom)) => {
+                            format!("\x1b[{}m▄\x1b[0m", fg(*bottom, style.colors))
+                        }
+                        (Some(top), Some(bottom)) => format!(
+                            "\x1b[{};{}m▀\x1b[0m",
+                            fg(*top, style.colors),
+                            bg(*bottom, style.colors)
+                        ),
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+        Glyphs::Braille => pixels
+            .chunks(4)
+            .map(|rows| {
+                let width = rows[0].len();
+                (0..width.div_ceil(2))
+                    .map(|col| {
+                        // bit of each dot, indexed by [row][column] within the 2x4 cell
+                        const DOTS: [[u32; 2]; 4] =
+                            [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+                        let mut bits = 0;
+                        for (row, dots) in rows.iter().zip(DOTS) {
+                            for (dx, dot) in dots.into_iter().enumerate() {
+                                if matches!(row.get(col * 2 + dx), Some(Some(_))) {
+                                    bits |= dot;
+                                }
+                            }
+                        }
+                        char::from_u32(0x2800 + bits).unwrap()
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+        Glyphs::Ascii => pixels
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|x| if x.is_some() { '#' } else { '.' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+    }
+}
+
+/// SGR parameters setting the foreground color
+fn fg(rgb888: Rgb888, colors: Colors) -> String {
+    match colors {
+        Colors::TrueColor => {
+            let [r, g, b] = rgb888.to_array();
+            format!("38;2;{r};{g};{b}")
+        }
+        Colors::Ansi256 => format!("38;5;{}", to_ansi256(rgb888)),
+        Colors::Ansi16 => match to_ansi16(rgb888) {
+            i @ 0..=7 => format!("{}", 30 + i),
+            i => format!("{}", 90 + i - 8),
+        },
+    }
+}
+
+/// SGR parameters setting the background color
+fn bg(rgb888: Rgb888, colors: Colors) -> String {
+    match colors {
+        Colors::TrueColor => {
+            let [r, g, b] = rgb888.to_array();
+            format!("48;2;{r};{g};{b}")
+        }
+        Colors::Ansi256 => format!("48;5;{}", to_ansi256(rgb888)),
+        Colors::Ansi16 => match to_ansi16(rgb888) {
+            i @ 0..=7 => format!("{}", 40 + i),
+            i => format!("{}", 100 + i - 8),
+        },
+    }
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (*a as i32 - b as i32).pow(2) as u32)
+        .sum()
+}
+
+/// Nearest color of the xterm 6x6x6 cube or grayscale ramp
+fn to_ansi256(rgb888: Rgb888) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let rgb = rgb888.to_array();
+
+    let nearest_level = |v: u8| {
+        (0..6)
+            .min_by_key(|i| (LEVELS[*i] as i32 - v as i32).abs())
+            .unwrap()
+    };
+    let [r, g, b] = rgb.map(nearest_level);
+    let cube = [LEVELS[r], LEVELS[g], LEVELS[b]];
+
+    let mean = rgb.iter().map(|v| *v as u32).sum::<u32>() / 3;
+    let gray_i = ((mean.saturating_sub(8) + 5) / 10).min(23) as u8;
+    let gray = 8 + 10 * gray_i;
+
+    if distance(rgb, [gray; 3]) < distance(rgb, cube) {
+        232 + gray_i
+    } else {
+        (16 + 36 * r + 6 * g + b) as u8
+    }
+}
+
+/// Nearest of the 16 standard terminal colors, in the usual xterm palette
+fn to_ansi16(rgb888: Rgb888) -> u8 {
+    const PALETTE: [[u8; 3]; 16] = [
+        [0, 0, 0],
+        [205, 0, 0],
+        [0, 205, 0],
+        [205, 205, 0],
+        [0, 0, 238],
+        [205, 0, 205],
+        [0, 205, 205],
+        [229, 229, 229],
+        [127, 127, 127],
+        [255, 0, 0],
+        [0, 255, 0],
+        [255, 255, 0],
+        [92, 92, 255],
+        [255, 0, 255],
+        [0, 255, 255],
+        [255, 255, 255],
+    ];
+    (0..16)
+        .min_by_key(|i| distance(rgb888.to_array(), PALETTE[*i as usize]))
+        .unwrap()
+}
+
+#[test]
+fn test_pixels_to_styled_string() {
+    let o = Some(Rgb888::new(255, 0, 0));
+    let x = None;
+    let pixels = vec![vec![o, x, x], vec![o, o, x], vec![x, o, x], vec![x, x, x]];
+
+    let ascii = TermStyle {
+        glyphs: Glyphs::Ascii,
+        colors: Colors::Ansi16,
+    };
+    assert_eq!(
+        pixels_to_styled_string(&pixels, &ascii),
+        "#..\n##.\n.#.\n..."
+    );
+
+    let braille = TermStyle {
+        glyphs: Glyphs::Braille,
+        ..ascii
+    };
+    assert_eq!(pixels_to_styled_string(&pixels, &braille), "⠳⠀");
+
+    let half_block = TermStyle {
+        glyphs: Glyphs::HalfBlock,
+        colors: Colors::Ansi16,
+    };
+    assert_eq!(
+        pixels_to_styled_string(&pixels, &half_block),
+        "\x1b[91;101m▀\x1b[0m\x1b[91m▄\x1b[0m \n \x1b[91m▀\x1b[0m "
+    );
+
+    assert_eq!(to_ansi256(Rgb888::new(255, 255, 0)), 226);
+    assert_eq!(to_ansi256(Rgb888::new(200, 200, 200)), 251);
+    assert_eq!(to_ansi16(Rgb888::new(204, 0, 0)), 1);
+}
@@ -8,7 +8,7 @@ pub struct Rgb888 {
 }
 
 impl Rgb888 {
-    pub fn new(r: u8, g: u8, b: u8) -> Rgb888 {
+    pub const fn new(r: u8, g: u8, b: u8) -> Rgb888 {
         Rgb888 { r, g, b }
     }
 
@@ -16,6 +16,10 @@ impl Rgb888 {
         Colour::RGB(self.r, self.g, self.b)
     }
 
+    pub fn to_array(self) -> [u8; 3] {
+        [self.r, self.g, self.b]
+    }
+
     pub fn to_rgb565(self) -> u16 {
         let r5 = ((self.r >> 3) as u16) << 11;
         let g6 = ((self.g >> 2) as u16) << 5;
@@ -25,6 +29,53 @@ impl Rgb888 {
     }
 }
 
+impl std::str::FromStr for Rgb888 {
+    type Err = String;
+
+    /// Parse a `#rrggbb` hex color
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6 && hex.is_ascii())
+            .ok_or_else(|| format!("invalid color {s:?}, expected #rrggbb"))?;
+        let channel = |i: usize| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| format!("invalid color {s:?}, expected #rrggbb"))
+        };
+        Ok(Rgb888::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl std::fmt::Display for Rgb888 {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+impl std::fmt::Debug for Rgb888 {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Rgb888 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[test]
+fn test_rgb888_from_str() {
+    let rgb: Rgb888 = "#FFc800".parse().unwrap();
+    assert_eq!(rgb, Rgb888::new(255, 200, 0));
+    assert_eq!(rgb.to_string(), "#ffc800");
+    assert!("ffc800".parse::<Rgb888>().is_err());
+    assert!("#ffc8".parse::<Rgb888>().is_err());
+    assert!("#ffc8zz".parse::<Rgb888>().is_err());
+}
+
 #[test]
 fn test_rgb888_to_rgb565() {
     assert_eq!(Rgb888::new(172, 10, 127).to_rgb565(), 43087);
@@ -0,0 +1,162 @@
+use std::{fmt, str::FromStr};
+
+/// How pixels are laid out as characters
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Glyphs {
+    /// Two `██` per pixel
+    Block,
+    /// `▀`/`▄` with fore- and background colors, two rows per line
+    HalfBlock,
+    /// Monochrome braille dots, 2x4 pixels per character
+    Braille,
+    /// Monochrome `#` and `.`, one per pixel, without any escape sequence
+    Ascii,
+}
+
+/// How many colors the terminal can show
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Colors {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+/// A terminal render mode, written as `<glyphs>[:<colors>]`, e.g. `half-block:256` or `ascii`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TermStyle {
+    pub glyphs: Glyphs,
+    pub colors: Colors,
+}
+
+impl Default for TermStyle {
+    fn default() -> Self {
+        TermStyle {
+            glyphs: Glyphs::Block,
+            colors: Colors::TrueColor,
+        }
+    }
+}
+
+impl TermStyle {
+    /// Best style for the current terminal, guessed from `COLORTERM` and `TERM`.
+    pub fn detect() -> Self {
+        Self::detect_from(
+            std::env::var("COLORTERM").ok().as_deref(),
+            std::env::var("TERM").ok().as_deref(),
+        )
+    }
+
+    fn detect_from(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        let colors = Self::detect_colors(colorterm, term);
+        let glyphs = match term {
+            None | Some("dumb") if colors != Colors::TrueColor => Glyphs::Ascii,
+            _ => Glyphs::Block,
+        };
+        TermStyle { glyphs, colors }
+    }
+
+    fn detect_colors(colorterm: Option<&str>, term: Option<&str>) -> Colors {
+        match (colorterm, term) {
+            (Some("truecolor" | "24bit"), _) => Colors::TrueColor,
+            (_, Some(term)) if term.contains("256color") => Colors::Ansi256,
+            _ => Colors::Ansi16,
+        }
+    }
+
+    /// Whether frames can be redrawn in place by moving the cursor
+    pub fn is_interactive(&self) -> bool {
+        self.glyphs != Glyphs::Ascii
+    }
+}
+
+impl FromStr for TermStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let detected = Self::detect();
+        let (glyphs, colors) = match s.split_once(':') {
+            Some((glyphs, colors)) => (glyphs, Some(colors)),
+            None => (s, None),
+        };
+        let glyphs = match glyphs {
+            "auto" => detected.glyphs,
+            "block" => Glyphs::Block,
+            "half-block" => Glyphs::HalfBlock,
+            "braille" => Glyphs::Braille,
+            "ascii" => Glyphs::Ascii,
+            _ => {
+                return Err(format!(
+                    "unknown glyphs {glyphs:?}, expected auto, block, half-block, braille or ascii"
+                ))
+            }
+        };
+        let colors = match colors {
+            None => detected.colors,
+            Some("truecolor" | "24bit") => Colors::TrueColor,
+            Some("256") => Colors::Ansi256,
+            Some("16") => Colors::Ansi16,
+            Some(colors) => {
+                return Err(format!(
+                    "unknown colors {colors:?}, expected truecolor, 256 or 16"
+                ))
+            }
+        };
+        Ok(TermStyle { glyphs, colors })
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for TermStyle {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for TermStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let glyphs = match self.glyphs {
+            Glyphs::Block => "block",
+            Glyphs::HalfBlock => "half-block",
+            Glyphs::Braille => "braille",
+            Glyphs::Ascii => "ascii",
+        };
+        let colors = match self.colors {
+            Colors::TrueColor => "truecolor",
+            Colors::Ansi256 => "256",
+            Colors::Ansi16 => "16",
+        };
+        write!(f, "{glyphs}:{colors}")
+    }
+}
+
+#[test]
+fn test_term_style() {
+    assert_eq!(
+        "half-block:256".parse::<TermStyle>().unwrap(),
+        TermStyle {
+            glyphs: Glyphs::HalfBlock,
+            colors: Colors::Ansi256
+        }
+    );
+    assert_eq!(
+        "auto:16".parse::<TermStyle>().unwrap().colors,
+        Colors::Ansi16
+    );
+    assert!("sixel".parse::<TermStyle>().is_err());
+    assert!("block:8".parse::<TermStyle>().is_err());
+
+    assert_eq!(
+        TermStyle::detect_from(Some("truecolor"), Some("xterm-256color")),
+        TermStyle::default()
+    );
+    assert_eq!(
+        TermStyle::detect_from(None, Some("xterm-256color")).colors,
+        Colors::Ansi256
+    );
+    assert_eq!(
+        TermStyle::detect_from(None, Some("dumb")).glyphs,
+        Glyphs::Ascii
+    );
+    assert_eq!(TermStyle::detect_from(None, None).glyphs, Glyphs::Ascii);
+}
@@ -0,0 +1,91 @@
+use super::Rgb888;
+
+/// The colors of a look, the matrix and the nixie underlight drawn with them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    /// Price chart and change, where the price went up
+    pub up: Rgb888,
+    /// Price chart and change, where the price went down
+    pub down: Rgb888,
+    /// Price chart and change, where the price didn't move
+    pub flat: Rgb888,
+    /// The price
+    pub text: Rgb888,
+    /// The time, and the ticker
+    pub clock: Rgb888,
+    /// The status dot
+    pub accent: Rgb888,
+    /// Behind everything; black leaves the pixels off
+    pub background: Rgb888,
+}
+
+/// The themes built in
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeName {
+    /// Yellow price, green and red chart
+    #[default]
+    Classic,
+    /// Cyan, magenta and acid green
+    Neon,
+    /// Shades of amber over a faint glow, as a VFD
+    Amber,
+    /// Shades of white
+    Monochrome,
+    /// Blue up and orange down, told apart with any color vision
+    Colorblind,
+}
+
+impl ThemeName {
+    pub fn theme(self) -> Theme {
+        let rgb = Rgb888::new;
+        match self {
+            ThemeName::Classic => Theme {
+                up: rgb(0, 204, 0),
+                down: rgb(204, 0, 0),
+                flat: rgb(0, 0, 204),
+                text: rgb(255, 255, 0),
+                clock: rgb(200, 200, 200),
+                accent: rgb(255, 255, 0),
+                background: rgb(0, 0, 0),
+            },
+            ThemeName::Neon => Theme {
+                up: rgb(57, 255, 20),
+                down: rgb(255, 20, 147),
+                flat: rgb(120, 60, 255),
+                text: rgb(0, 240, 255),
+                clock: rgb(255, 0, 200),
+                accent: rgb(255, 230, 0),
+                background: rgb(0, 0, 0),
+            },
+            ThemeName::Amber => Theme {
+                up: rgb(255, 200, 60),
+                down: rgb(170, 90, 0),
+                flat: rgb(120, 70, 0),
+                text: rgb(255, 176, 0),
+                clock: rgb(200, 130, 0),
+                accent: rgb(255, 176, 0),
+                background: rgb(16, 8, 0),
+            },
+            ThemeName::Monochrome => Theme {
+                up: rgb(255, 255, 255),
+                down: rgb(110, 110, 110),
+                flat: rgb(60, 60, 60),
+                text: rgb(255, 255, 255),
+                clock: rgb(160, 160, 160),
+                accent: rgb(255, 255, 255),
+                background: rgb(0, 0, 0),
+            },
+            // from the Okabe-Ito palette
+            ThemeName::Colorblind => Theme {
+                up: rgb(0, 114, 178),
+                down: rgb(230, 159, 0),
+                flat: rgb(153, 153, 153),
+                text: rgb(240, 228, 66),
+                clock: rgb(200, 200, 200),
+                accent: rgb(86, 180, 233),
+                background: rgb(0, 0, 0),
+            },
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use super::character::{Character, Font};
+use super::rgb::Rgb888;
+
+type Pixels = Vec<Vec<Option<Rgb888>>>;
+
+#[derive(Copy, Clone, Debug, PartialEq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transition {
+    /// Replace the digits instantly
+    None,
+    /// Odometer-style: changed digits roll up (or down) into place
+    Roll,
+    /// Split-flap: the top half flips over before the bottom half
+    Flip,
+}
+
+impl Transition {
+    /// Intermediate renderings between the texts `from` and `to`, ending with `to` itself.
+    ///
+    /// Only the glyphs that differ are animated. If the two texts can't be lined up glyph by
+    /// glyph (e.g. the number gained a digit), the result is just the final rendering.
+    pub fn frames(&self, from: &str, to: &str, font: Font) -> Vec<Character> {
+        let last = Character::from_string(to, font);
+        let from: Vec<char> = from.chars().collect();
+        let to: Vec<char> = to.chars().collect();
+
+        let aligned = from.len() == to.len()
+            && from.iter().zip(&to).all(|(a, b)| {
+                Character::new(*a, font).pixels[0].len() == Character::new(*b, font).pixels[0].len()
+            });
+        let n_frames = match self {
+            Transition::None => 0,
+            Transition::Roll => Character::new('|', font).pixels.len(),
+            Transition::Flip => 2,
+        };
+        if !aligned || from == to {
+            return vec![last];
+        }
+
+        let mut frames: Vec<Character> = (1..=n_frames)
+            .map(|step| {
+                from.iter()
+                    .zip(&to)
+                    .map(|(a, b)| {
+                        let pixels = if a == b {
+                            Character::new(*b, font).pixels
+                        } else {
+                            let (old, new) = (
+                                Character::new(*a, font).pixels,
+                                Character::new(*b, font).pixels,
+                            );
+                            match self {
+                                Transition::Roll => roll(&old, &new, step, b > a),
+                                _ => flip(&old, &new, step),
+                            }
+                        };
+                        Character { font, pixels }
+                    })
+                    .reduce(|a, b| a + b)
+                    .unwrap()
+            })
+            .collect();
+        frames.push(last);
+        frames
+    }
+}
+
+/// `step` rows into scrolling from `old` to `new`, with a blank row between the two glyphs.
+fn roll(old: &Pixels, new: &Pixels, step: usize, up: bool) -> Pixels {
+    let blank = vec![vec![None; old[0].len()]];
+    let strip: Pixels = if up {
+        [old.clone(), blank, new.clone()].concat()
+    } else {
+        [new.clone(), blank, old.clone()].concat()
+    };
+    let height = old.len();
+    let offset = if up { step } else { height + 1 - step };
+    strip[offset..offset + height].to_vec()
+}
+
+/// Step 1 folds the top half of `old` down, step 2 shows the top of `new` over the bottom of `old`.
+fn flip(old: &Pixels, new: &Pixels, step: usize) -> Pixels {
+    let mid = old.len() / 2;
+    let top: Pixels = if step == 1 {
+        let mut top = old[..mid].to_vec();
+        top[0] = vec![None; old[0].len()];
+        top
+    } else {
+        new[..mid].to_vec()
+    };
+    [top, old[mid..].to_vec()].concat()
+}
+
+#[test]
+fn test_transition_frames() {
+    let to = Character::from_string("12.35", Font::Small);
+
+    let frames = Transition::Roll.frames("12.34", "12.35", Font::Small);
+    assert_eq!(frames.len(), 4);
+    assert!(frames.last().unwrap().pixels == to.pixels);
+    // unchanged glyphs stay put
+    for frame in &frames {
+        for row in 0..3 {
+            assert!(frame.pixels[row][..13] == to.pixels[row][..13]);
+        }
+    }
+    // half way through the roll, the bottom row of "4" sits at the top
+    assert!(frames[1].pixels[0][14..] == Character::new('4', Font::Small).pixels[2][..]);
+
+    assert_eq!(
+        Transition::Flip.frames("12.34", "12.35", Font::Small).len(),
+        3
+    );
+    assert_eq!(
+        Transition::None.frames("12.34", "12.35", Font::Small).len(),
+        1
+    );
+    // misaligned texts jump straight to the end
+    assert_eq!(
+        Transition::Roll.frames("9.99", "10.00", Font::Small).len(),
+        1
+    );
+}
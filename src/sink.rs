@@ -0,0 +1,468 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{broadcast, watch};
+
+use super::{
+    feed::{recv_latest_counted, Feed, Frame},
+    metrics::METRICS,
+    ws_coin::{Market, Price},
+};
+
+/// A device showing the feed: a LED matrix, nixie tubes, the terminal...
+///
+/// Implementations only talk to their device. Rate limiting, skipping the frames a device is too
+/// slow for, and reporting its errors are left to [`drive`].
+pub trait DisplaySink: Send {
+    /// Name of the device in logs
+    fn name(&self) -> String;
+
+    /// Shortest time between two renders; frames coming in faster are skipped
+    fn min_interval(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn render(&mut self, frame: &Frame) -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    /// Set the brightness, from 0 (off) to 255 (full). Devices without a dimmer ignore it.
+    fn set_brightness(
+        &mut self,
+        _brightness: u8,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Show the message of an alert that fired. Devices that can't show text ignore it.
+    fn alert(&mut self, _message: &str) -> impl Future<Output = anyhow::Result<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Follow a price received, of any market followed, even while blanked. Most devices only
+    /// show the frames, and ignore it.
+    fn price(&mut self, _price: &Price) -> impl Future<Output = anyhow::Result<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Follow the markets, the first one being shown, once the sink is driven and whenever they're
+    /// changed, e.g. by a reload. Most devices only show the frames, and ignore them.
+    fn markets(&mut self, _markets: &[Market]) -> impl Future<Output = anyhow::Result<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Blank the display
+    fn clear(&mut self) -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    /// Release the device before exiting, e.g. blank it or give it back to its own apps
+    fn shutdown(&mut self) -> impl Future<Output = anyhow::Result<()>> + Send;
+}
+
+/// Logs a failing device once rather than on every frame, until it recovers
+struct ErrorReport {
+    name: String,
+    last_error: Option<String>,
+}
+
+impl ErrorReport {
+    fn report(&mut self, result: anyhow::Result<()>) {
+        match result {
+            Ok(()) => {
+                if self.last_error.take().is_some() {
+                    log::info!("{} recovered", self.name);
+                }
+            }
+            Err(e) => {
+                let e = format!("{e:#}");
+                if self.last_error.as_ref() != Some(&e) {
+                    log::warn!("Failed to render on {}: {e}", self.name);
+                }
+                self.last_error = Some(e);
+            }
+        }
+    }
+}
+
+/// What a sink is driven with, subscribed to from the [`Feed`]
+pub struct FeedSubscription {
+    pub frames: broadcast::Receiver<Frame>,
+    pub alerts: broadcast::Receiver<String>,
+    pub prices: broadcast::Receiver<Price>,
+    pub markets: watch::Receiver<Vec<Market>>,
+}
+
+impl FeedSubscription {
+    pub fn to(feed: &Feed) -> Self {
+        FeedSubscription {
+            frames: feed.frames(),
+            alerts: feed.alerts(),
+            prices: feed.prices(),
+            markets: feed.settings().subscribe_markets(),
+        }
+    }
+}
+
+/// Render the frames, show the alerts and pass every price and the markets followed of `feed` to
+/// `sink` until `stop` turns true or the feed closes, then shut it down. The sink is set to `brightness` once it's given, and whenever it's changed. While
+/// `blanked`, it's cleared and left so, the frames and alerts being skipped.
+pub async fn drive<S: DisplaySink>(
+    mut sink: S,
+    feed: FeedSubscription,
+    mut brightness: watch::Receiver<Option<u8>>,
+    mut blanked: watch::Receiver<bool>,
+    mut stop: watch::Receiver<bool>,
+) -> anyhow::Result<()> {
+    let FeedSubscription {
+        mut frames,
+        mut alerts,
+        mut prices,
+        mut markets,
+    } = feed;
+    let name = sink.name();
+    let mut errors = ErrorReport {
+        name: name.clone(),
+        last_error: None,
+    };
+    let followed = markets.borrow_and_update().clone();
+    errors.report(sink.markets(&followed).await);
+    let initial = *brightness.borrow_and_update();
+    if let Some(level) = initial {
+        errors.report(sink.set_brightness(level).await);
+    }
+    let mut blank = *blanked.borrow_and_update();
+    if blank {
+        errors.report(sink.clear().await);
+    }
+    loop {
+        let frame = tokio::select! {
+            frame = recv_latest_counted(&mut frames) => match frame {
+                Some((frame, skipped)) => {
+                    if skipped > 0 {
+                        METRICS.frames_dropped(&name, skipped);
+                    }
+                    frame
+                }
+                None => break,
+            },
+            // unlike frames, every alert is shown
+            Ok(message) = alerts.recv() => {
+                if !blank {
+                    errors.report(sink.alert(&message).await);
+                }
+                continue;
+            }
+            Ok(price) = prices.recv() => {
+                errors.report(sink.price(&price).await);
+                continue;
+            }
+            followed = next(&mut markets) => {
+                errors.report(sink.markets(&followed).await);
+                continue;
+            }
+            level = changed(&mut brightness) => {
+                errors.report(sink.set_brightness(level).await);
+                continue;
+            }
+            now_blank = next(&mut blanked) => {
+                if now_blank && !blank {
+                    errors.report(sink.clear().await);
+                }
+                // otherwise the next frame is rendered as usual
+                blank = now_blank;
+                continue;
+            }
+            _ = stopped(&mut stop) => break,
+        };
+        if blank {
+            continue;
+        }
+        let next_render = tokio::time::Instant::now() + sink.min_interval();
+        let rendered = sink.render(&frame).await;
+        if rendered.is_ok() {
+            METRICS.frame_rendered(&name);
+        }
+        errors.report(rendered);
+        // frames coming in meanwhile are skipped, but for the latest one
+        tokio::select! {
+            _ = tokio::time::sleep_until(next_render) => {},
+            _ = stopped(&mut stop) => break,
+        }
+    }
+    sink.shutdown()
+        .await
+        .map_err(|e| e.context(format!("Failed to shut down {name}")))
+}
+
+/// Resolves once `stop` turns true, or its sender is gone
+async fn stopped(stop: &mut watch::Receiver<bool>) {
+    // the returned guard isn't `Send`, and mustn't be held across the awaits of `drive`
+    let _ = stop.wait_for(|stop| *stop).await;
+}
+
+/// The next brightness given, never resolving once the sender is gone
+async fn changed(brightness: &mut watch::Receiver<Option<u8>>) -> u8 {
+    while brightness.changed().await.is_ok() {
+        if let Some(level) = *brightness.borrow_and_update() {
+            return level;
+        }
+    }
+    std::future::pending().await
+}
+
+/// The next value of `watched`, e.g. whether the outputs are blanked; never resolving once the
+/// sender is gone
+async fn next<T: Clone>(watched: &mut watch::Receiver<T>) -> T {
+    if watched.changed().await.is_ok() {
+        return watched.borrow_and_update().clone();
+    }
+    std::future::pending().await
+}
+
+/// Sets the brightness of every output at once, and blanks the devices with a dimmer.
+#[derive(Clone)]
+pub struct Dimmer {
+    // the brightness of each output, a device only being set once it's given one
+    outputs: Arc<Mutex<Vec<watch::Sender<Option<u8>>>>>,
+    level: Arc<Mutex<Option<u8>>>,
+    blanked: watch::Sender<bool>,
+}
+
+impl Default for Dimmer {
+    fn default() -> Self {
+        Dimmer {
+            outputs: Arc::default(),
+            level: Arc::default(),
+            blanked: watch::Sender::new(false),
+        }
+    }
+}
+
+impl Dimmer {
+    /// From 0 (off) to 255 (full), see [`DisplaySink::set_brightness`]
+    pub fn set(&self, level: u8) {
+        *self.level.lock().unwrap() = Some(level);
+        for output in self.outputs.lock().unwrap().iter() {
+            output.send_replace(Some(level));
+        }
+    }
+
+    /// The brightness last set, if any
+    pub fn level(&self) -> Option<u8> {
+        *self.level.lock().unwrap()
+    }
+
+    /// Turn the devices added with [`Drivers::add_dimmed`] off, or back on, the feed going on
+    pub fn set_blanked(&self, blanked: bool) {
+        self.blanked.send_replace(blanked);
+    }
+
+    pub fn is_blanked(&self) -> bool {
+        *self.blanked.borrow()
+    }
+}
+
+/// Every output of the process, each driven on its own task from the same feed.
+pub struct Drivers {
+    feed: Feed,
+    dimmer: Dimmer,
+    stop: watch::Sender<bool>,
+    tasks: Vec<tokio::task::JoinHandle<anyhow::Result<()>>>,
+}
+
+impl Drivers {
+    pub fn new(feed: Feed) -> Self {
+        Drivers {
+            feed,
+            dimmer: Dimmer::default(),
+            stop: watch::Sender::new(false),
+            tasks: Vec::new(),
+        }
+    }
+
+    pub fn feed(&self) -> &Feed {
+        &self.feed
+    }
+
+    /// Dims the outputs added so far, and those added later
+    pub fn dimmer(&self) -> Dimmer {
+        self.dimmer.clone()
+    }
+
+    pub fn add<S: DisplaySink + 'static>(&mut self, sink: S) {
+        // never blanked, e.g. the terminal stays readable
+        let blanked = watch::Sender::new(false).subscribe();
+        self.spawn(sink, watch::Sender::new(None), blanked);
+    }
+
+    /// Add `sink`, a device with a dimmer, setting it to the level of `brightness` too, e.g. as
+    /// set in the config file. The [`Dimmer`] overrides it, and blanks it.
+    pub fn add_dimmed<S: DisplaySink + 'static>(
+        &mut self,
+        sink: S,
+        brightness: watch::Sender<Option<u8>>,
+    ) {
+        let blanked = self.dimmer.blanked.subscribe();
+        self.spawn(sink, brightness, blanked);
+    }
+
+    fn spawn<S: DisplaySink + 'static>(
+        &mut self,
+        sink: S,
+        brightness: watch::Sender<Option<u8>>,
+        blanked: watch::Receiver<bool>,
+    ) {
+        let task = drive(
+            sink,
+            FeedSubscription::to(&self.feed),
+            brightness.subscribe(),
+            blanked,
+            self.stop.subscribe(),
+        );
+        self.dimmer.outputs.lock().unwrap().push(brightness);
+        self.tasks.push(tokio::spawn(task));
+    }
+
+    /// Run until `until` resolves, then shut every output down. Returns the errors of the outputs
+    /// that failed to.
+    pub async fn run_until(self, until: impl Future) -> Vec<anyhow::Error> {
+        until.await;
+        self.stop.send_replace(true);
+        let mut errors = Vec::new();
+        for result in futures::future::join_all(self.tasks).await {
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => errors.push(e),
+                Err(e) => errors.push(anyhow::anyhow!("Output task failed: {e}")),
+            }
+        }
+        errors
+    }
+}
+
+// on paused time, the sleeps advance the clock only once the driver is idle
+#[tokio::test(start_paused = true)]
+async fn test_drive() {
+    use super::{screen::Screen, ws_coin::ConnectionState};
+    use rust_decimal::Decimal;
+
+    /// Records the price of each frame rendered, each price followed, each brightness set and
+    /// each clear
+    struct Recorder {
+        rendered: Arc<Mutex<Vec<i64>>>,
+        followed: Arc<Mutex<Vec<i64>>>,
+        levels: Arc<Mutex<Vec<u8>>>,
+        cleared: Arc<Mutex<usize>>,
+        shut_down: Arc<Mutex<bool>>,
+    }
+
+    impl DisplaySink for Recorder {
+        fn name(&self) -> String {
+            "recorder".to_string()
+        }
+
+        fn min_interval(&self) -> Duration {
+            Duration::from_millis(100)
+        }
+
+        async fn render(&mut self, frame: &Frame) -> anyhow::Result<()> {
+            let price = frame.price.as_ref().unwrap().price.try_into()?;
+            self.rendered.lock().unwrap().push(price);
+            Ok(())
+        }
+
+        async fn price(&mut self, price: &super::ws_coin::Price) -> anyhow::Result<()> {
+            self.followed.lock().unwrap().push(price.price.try_into()?);
+            Ok(())
+        }
+
+        async fn set_brightness(&mut self, brightness: u8) -> anyhow::Result<()> {
+            self.levels.lock().unwrap().push(brightness);
+            Ok(())
+        }
+
+        async fn clear(&mut self) -> anyhow::Result<()> {
+            *self.cleared.lock().unwrap() += 1;
+            Ok(())
+        }
+
+        async fn shutdown(&mut self) -> anyhow::Result<()> {
+            *self.shut_down.lock().unwrap() = true;
+            Ok(())
+        }
+    }
+
+    let price = |price: i64| super::ws_coin::Price {
+        symbol: "BTCUSDT".to_string(),
+        name: "BTC".to_string(),
+        price: Decimal::from(price),
+        change_24h: None,
+    };
+    let frame = |p: i64| Frame {
+        screen: Screen::default(),
+        price: Some(price(p)),
+        new_price: true,
+        connection: ConnectionState::Connected,
+    };
+
+    let (frames, rx) = broadcast::channel(16);
+    let (_alerts, alerts_rx) = broadcast::channel(16);
+    let (prices, prices_rx) = broadcast::channel(16);
+    let (brightness, dimmer) = watch::channel(Some(255));
+    let (blank, blanked) = watch::channel(false);
+    let (stop, stopped) = watch::channel(false);
+    let rendered = Arc::new(Mutex::new(Vec::new()));
+    let followed = Arc::new(Mutex::new(Vec::new()));
+    let levels = Arc::new(Mutex::new(Vec::new()));
+    let cleared = Arc::new(Mutex::new(0));
+    let shut_down = Arc::new(Mutex::new(false));
+    let task = tokio::spawn(drive(
+        Recorder {
+            rendered: rendered.clone(),
+            followed: followed.clone(),
+            levels: levels.clone(),
+            cleared: cleared.clone(),
+            shut_down: shut_down.clone(),
+        },
+        FeedSubscription {
+            frames: rx,
+            alerts: alerts_rx,
+            prices: prices_rx,
+            markets: watch::channel(Vec::new()).1,
+        },
+        dimmer,
+        blanked,
+        stopped,
+    ));
+
+    assert!(frames.send(frame(1)).is_ok());
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    // sent while the recorder waits for its min interval, only the last one is rendered
+    for price in [2, 3, 4] {
+        assert!(frames.send(frame(price)).is_ok());
+    }
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    brightness.send_replace(None); // no change
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    brightness.send_replace(Some(128));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    // frames are skipped while blanked, until the next one after
+    blank.send_replace(true);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(frames.send(frame(5)).is_ok());
+    // unlike frames, prices are followed while blanked, and never skipped
+    for p in [5, 6, 7] {
+        assert!(prices.send(price(p)).is_ok());
+    }
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    blank.send_replace(false);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(frames.send(frame(6)).is_ok());
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    stop.send_replace(true);
+    task.await.unwrap().unwrap();
+
+    assert_eq!(*rendered.lock().unwrap(), [1, 4, 6]);
+    assert_eq!(*followed.lock().unwrap(), [5, 6, 7]);
+    assert_eq!(*levels.lock().unwrap(), [255, 128]);
+    assert_eq!(*cleared.lock().unwrap(), 1);
+    assert!(*shut_down.lock().unwrap());
+}
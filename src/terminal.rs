@@ -0,0 +1,292 @@
+use std::collections::VecDeque;
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+
+use crossterm::{cursor, execute, queue, terminal};
+use tokio::sync::watch;
+
+use super::{
+    feed::Frame,
+    screen::{term_style::TermStyle, Screen},
+    sink::DisplaySink,
+};
+
+const LOG_PANE_CAPACITY: usize = 200;
+
+/// The latest log lines, shown below the matrix instead of being printed over it.
+#[derive(Clone, Default)]
+pub struct LogPane {
+    lines: Arc<Mutex<Lines>>,
+}
+
+#[derive(Default)]
+struct Lines {
+    last: VecDeque<String>,
+    received: usize,
+    detached: bool, // the terminal is restored, lines go to stderr
+}
+
+impl LogPane {
+    /// A writer appending to this pane, to plug into a logger
+    pub fn writer(&self) -> LogPaneWriter {
+        LogPaneWriter {
+            pane: self.clone(),
+            partial: Vec::new(),
+        }
+    }
+
+    fn last(&self, n: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        lines
+            .last
+            .iter()
+            .skip(lines.last.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+
+    /// How many lines were written so far
+    fn received(&self) -> usize {
+        self.lines.lock().unwrap().received
+    }
+
+    /// Print the lines received after the first `seen` ones to stderr, and the next ones too, as
+    /// the pane isn't shown anymore
+    fn detach(&self, seen: usize) {
+        let mut lines = self.lines.lock().unwrap();
+        let unseen = lines.received.saturating_sub(seen).min(lines.last.len());
+        for line in lines.last.iter().skip(lines.last.len() - unseen) {
+            eprintln!("{line}");
+        }
+        lines.detached = true;
+    }
+}
+
+pub struct LogPaneWriter {
+    pane: LogPane,
+    partial: Vec<u8>, // bytes of an unfinished line
+}
+
+impl Write for LogPaneWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.partial.extend_from_slice(buf);
+        while let Some(i) = self.partial.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=i).collect();
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+            let mut lines = self.pane.lines.lock().unwrap();
+            if lines.detached {
+                eprintln!("{line}");
+                continue;
+            }
+            if lines.last.len() == LOG_PANE_CAPACITY {
+                lines.last.pop_front();
+            }
+            lines.last.push_back(line);
+            lines.received += 1;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Draws frames on the terminal.
+///
+/// On an interactive terminal this takes over the alternate screen: the matrix at the top, then a
+/// status line and the log pane, only rewriting the lines that changed between two draws. The
+/// previous screen and the cursor are restored when dropped. Otherwise (plain styles, or stdout is
+/// not a terminal) frames are simply appended to stdout.
+pub struct Terminal {
+    style: TermStyle,
+    log_pane: Option<LogPane>, // only in fullscreen mode
+    drawn: Vec<String>,
+    size: (u16, u16),
+    devices: Vec<(String, watch::Receiver<bool>)>, // shown as online/offline in the status line
+    seen: usize,                                   // log lines received by the last draw
+    matrix: (usize, usize),                        // size of the last frame, to clear as much
+}
+
+impl Terminal {
+    pub fn new(style: TermStyle) -> io::Result<Self> {
+        let fullscreen = style.is_interactive() && io::stdout().is_terminal();
+        if fullscreen {
+            execute!(
+                io::stdout(),
+                terminal::EnterAlternateScreen,
+                cursor::Hide,
+                terminal::Clear(terminal::ClearType::All)
+            )?;
+            // leave the alternate screen before the panic message is printed, or it'd be lost
+            let hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                restore();
+                hook(info);
+            }));
+        }
+        Ok(Terminal {
+            style,
+            log_pane: fullscreen.then(LogPane::default),
+            drawn: Vec::new(),
+            size: (0, 0),
+            devices: Vec::new(),
+            seen: 0,
+            matrix: Screen::default().size(),
+        })
+    }
+
+    /// Show whether the device `name` is online in the status line
+    pub fn watch_device(&mut self, name: &str, online: watch::Receiver<bool>) {
+        self.devices.push((name.to_string(), online));
+    }
+
+    /// Where logs should go instead of stdout, if the terminal is taken over
+    pub fn log_pane(&self) -> Option<LogPane> {
+        self.log_pane.clone()
+    }
+
+    pub fn draw(&mut self, screen: &Screen, status: &str) -> io::Result<()> {
+        let matrix = screen.to_term_string(&self.style);
+        let Some(log_pane) = &self.log_pane else {
+            if self.style.is_interactive() {
+                println!("{matrix}");
+            } else {
+                println!("{matrix}\n");
+            }
+            return Ok(());
+        };
+
+        let size = match terminal::size()? {
+            (0, _) | (_, 0) => (80, 24), // e.g. a pty nobody set the size of
+            size => size,
+        };
+        let (width, height) = (size.0 as usize, size.1 as usize);
+        let mut lines: Vec<String> = matrix.lines().map(|l| truncate(l, width)).collect();
+        lines.push(String::new());
+        lines.push(truncate(status, width));
+        lines.push("─".repeat(width));
+        let log_lines = height.saturating_sub(lines.len());
+        self.seen = log_pane.received();
+        lines.extend(log_pane.last(log_lines).iter().map(|l| truncate(l, width)));
+        lines.truncate(height);
+
+        let mut stdout = io::stdout().lock();
+        if size != self.size {
+            queue!(stdout, terminal::Clear(terminal::ClearType::All))?;
+            self.drawn.clear();
+            self.size = size;
+        }
+        for (row, line) in lines.iter().enumerate() {
+            if self.drawn.get(row) != Some(line) {
+                queue!(stdout, cursor::MoveTo(0, row as u16))?;
+                write!(stdout, "{line}")?;
+                queue!(stdout, terminal::Clear(terminal::ClearType::UntilNewLine))?;
+            }
+        }
+        for row in lines.len()..self.drawn.len() {
+            queue!(
+                stdout,
+                cursor::MoveTo(0, row as u16),
+                terminal::Clear(terminal::ClearType::CurrentLine)
+            )?;
+        }
+        stdout.flush()?;
+        self.drawn = lines;
+        Ok(())
+    }
+}
+
+impl DisplaySink for Terminal {
+    fn name(&self) -> String {
+        "terminal".to_string()
+    }
+
+    async fn render(&mut self, frame: &Frame) -> anyhow::Result<()> {
+        let mut status = frame.connection.to_string();
+        for (name, online) in &self.devices {
+            let online = if *online.borrow() {
+                "online"
+            } else {
+                "offline"
+            };
+            status += &format!(" | {name} {online}");
+        }
+        self.matrix = frame.screen.size();
+        Ok(self.draw(&frame.screen, &status)?)
+    }
+
+    async fn clear(&mut self) -> anyhow::Result<()> {
+        let (width, height) = self.matrix;
+        Ok(self.draw(&Screen::new(width, height), "")?)
+    }
+
+    async fn shutdown(&mut self) -> anyhow::Result<()> {
+        Ok(()) // the terminal is restored when dropped
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if let Some(log_pane) = &self.log_pane {
+            restore();
+            // e.g. errors of the shutdown, logged after the last draw
+            log_pane.detach(self.seen);
+        }
+    }
+}
+
+fn restore() {
+    let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+}
+
+/// The first `width` columns of `s`, keeping its color escape sequences whole
+fn truncate(s: &str, width: usize) -> String {
+    let mut clipped = String::new();
+    let (mut shown, mut escaped) = (0, false);
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // up to the final letter of the sequence, e.g. "\x1b[38;2;255;0;0m"
+            clipped.push(c);
+            for c in chars.by_ref() {
+                clipped.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            escaped = true;
+        } else if shown < width {
+            clipped.push(c);
+            shown += 1;
+        } else {
+            break;
+        }
+    }
+    if escaped && clipped.len() < s.len() {
+        clipped.push_str("\x1b[0m");
+    }
+    clipped
+}
+
+#[test]
+fn test_log_pane() {
+    let pane = LogPane::default();
+    let mut writer = pane.writer();
+    write!(writer, "first\nsec").unwrap();
+    assert_eq!(pane.last(5), ["first"]);
+    writeln!(writer, "ond").unwrap();
+    for i in 0..LOG_PANE_CAPACITY {
+        writeln!(writer, "{i}").unwrap();
+    }
+    assert_eq!(pane.last(2), ["198", "199"]);
+    assert_eq!(pane.received(), LOG_PANE_CAPACITY + 2);
+}
+
+#[test]
+fn test_truncate() {
+    assert_eq!(truncate("status line", 6), "status");
+    let red = "\x1b[38;2;255;0;0m██\x1b[0m";
+    assert_eq!(truncate(red, 5), red);
+    assert_eq!(truncate(red, 1), "\x1b[38;2;255;0;0m█\x1b[0m");
+}
@@ -0,0 +1,154 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::{Html, IntoResponse},
+    routing::get,
+    Router,
+};
+use tokio::sync::watch;
+
+use super::{
+    feed::Frame,
+    screen::{Rgb888, Screen},
+    sink::DisplaySink,
+};
+
+const INDEX_HTML: &str = include_str!("index.html");
+
+/// The live preview as an output: serves a page drawing the LED matrix in a canvas, kept up to date
+/// with the frames rendered to it.
+pub struct WebPreview {
+    addr: SocketAddr,
+    screens: watch::Sender<Option<Screen>>,
+    server: tokio::task::JoinHandle<std::io::Result<()>>,
+}
+
+impl WebPreview {
+    /// Start serving on `addr`, failing right away if it can't be bound.
+    pub async fn bind(addr: SocketAddr) -> anyhow::Result<Self> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        log::info!("Serving live preview on http://{addr}");
+        let screens = watch::Sender::new(None);
+        let app = router(screens.subscribe());
+        let server = tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok(WebPreview {
+            addr,
+            screens,
+            server,
+        })
+    }
+}
+
+impl DisplaySink for WebPreview {
+    fn name(&self) -> String {
+        format!("live preview on http://{}", self.addr)
+    }
+
+    async fn render(&mut self, frame: &Frame) -> anyhow::Result<()> {
+        anyhow::ensure!(!self.server.is_finished(), "the server stopped");
+        self.screens.send_replace(Some(frame.screen.clone()));
+        Ok(())
+    }
+
+    async fn clear(&mut self) -> anyhow::Result<()> {
+        // blank at the size of the last frame, if any was shown
+        self.screens.send_modify(|screen| {
+            if let Some(screen) = screen {
+                *screen = Screen::new(screen.width(), screen.height());
+            }
+        });
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.server.abort();
+        Ok(())
+    }
+}
+
+pub fn router(screens: watch::Receiver<Option<Screen>>) -> Router {
+    Router::new()
+        .route("/", get(|| async { Html(INDEX_HTML) }))
+        .route("/ws", get(ws_handler))
+        .with_state(screens)
+}
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(screens): State<watch::Receiver<Option<Screen>>>,
+) -> impl IntoResponse {
+    ws.on_upgrade(|socket| push_screens(socket, screens))
+}
+
+async fn push_screens(mut socket: WebSocket, mut screens: watch::Receiver<Option<Screen>>) {
+    log::info!("Live preview client connected");
+    // send the current screen right away, then every new one
+    screens.mark_changed();
+    while screens.changed().await.is_ok() {
+        let Some(frame) = screens.borrow_and_update().as_ref().map(encode) else {
+            continue;
+        };
+        if socket.send(Message::Binary(frame)).await.is_err() {
+            break;
+        }
+    }
+    log::info!("Live preview client disconnected");
+}
+
+/// The width and height as big-endian `u16`, followed by the RGB bytes of every pixel, row by row;
+/// unlit pixels are black.
+fn encode(screen: &Screen) -> Vec<u8> {
+    let mut frame = [screen.width() as u16, screen.height() as u16]
+        .iter()
+        .flat_map(|size| size.to_be_bytes())
+        .collect::<Vec<u8>>();
+    for pixel in screen.pixels.iter().flatten() {
+        frame.extend_from_slice(&pixel.unwrap_or(Rgb888::new(0, 0, 0)).to_array());
+    }
+    frame
+}
+
+#[tokio::test]
+async fn test_serve() {
+    use futures::StreamExt as _;
+    use tokio_tungstenite::tungstenite;
+
+    let (tx, rx) = watch::channel(None);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router(rx)).await });
+
+    let page = reqwest::get(format!("http://{addr}/"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("<canvas"));
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/ws"))
+        .await
+        .unwrap();
+    let mut screen = Screen::default();
+    screen.draw(&[vec![Some(Rgb888::new(1, 2, 3))]], 1, 0);
+    tx.send(Some(screen)).unwrap();
+
+    let Some(Ok(tungstenite::Message::Binary(frame))) = ws.next().await else {
+        panic!("expected a binary frame");
+    };
+    assert_eq!(frame.len(), 4 + 32 * 8 * 3);
+    assert_eq!(frame[..10], [0, 32, 0, 8, 0, 0, 0, 1, 2, 3]);
+}
+
+#[test]
+fn test_encode() {
+    // as wide as a layout may be
+    let frame = encode(&Screen::new(256, 1));
+    assert_eq!(frame[..4], [1, 0, 0, 1]);
+    assert_eq!(frame.len(), 4 + 256 * 3);
+}
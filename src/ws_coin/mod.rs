@@ -3,25 +3,55 @@ pub mod parse_json;
 use futures::{SinkExt, Stream, StreamExt};
 use parse_json::{parse_json, Msg};
 use rust_decimal::prelude::*;
-use std::{error::Error, fmt, time::Duration};
+use std::{collections::HashMap, error::Error, fmt, time::Duration};
 use tokio::net::TcpStream;
 use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
 use url::Url;
 
+use crate::metrics::METRICS;
+
 type PriceSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Price {
+    /// Binance symbol of the market, e.g. "BTCUSDT"
+    pub symbol: String,
+    /// Name of the market shown, e.g. "BTC"
     pub name: String,
     pub price: Decimal,
+    /// Change over the last 24h in percent, once known
+    pub change_24h: Option<Decimal>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Market {
+    /// Binance symbol, e.g. "BTCUSDT"
     pub symbol: String,
+    /// Short name to show, e.g. "BTC"
     pub name: String,
 }
 
+/// Where prices come from
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Source {
+    /// Binance WebSocket endpoint
+    pub url: String,
+    /// How long to wait before reconnecting after an error
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub reconnect_delay: Duration,
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Source {
+            url: "wss://stream.binance.com/ws".to_string(),
+            reconnect_delay: Duration::from_secs(60),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum RecvError {
     Timeout,
@@ -45,27 +75,89 @@ impl fmt::Display for RecvError {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    /// Waiting to reconnect after the given error
+    Disconnected(String),
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectionState::Connecting => write!(f, "Connecting..."),
+            ConnectionState::Connected => write!(f, "Connected"),
+            ConnectionState::Disconnected(err) => write!(f, "Disconnected ({err})"),
+        }
+    }
+}
+
 pub struct WsCoin {
     rx: tokio::sync::mpsc::UnboundedReceiver<Price>,
+    state: tokio::sync::watch::Receiver<ConnectionState>,
+    markets: tokio::sync::watch::Sender<Vec<Market>>,
+    task: tokio::task::JoinHandle<()>,
 }
 impl WsCoin {
     pub async fn new(markets: Vec<Market>) -> Self {
+        Self::with_source(markets, Source::default()).await
+    }
+
+    pub async fn with_source(markets: Vec<Market>, source: Source) -> Self {
         let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let (state_tx, state) = tokio::sync::watch::channel(ConnectionState::Connecting);
+        let (markets_tx, mut markets_rx) = tokio::sync::watch::channel(markets);
 
-        tokio::spawn(async move {
+        let task = tokio::spawn(async move {
+            let mut first = true;
             loop {
-                let mut socket = match connect(&markets).await {
+                if !std::mem::take(&mut first) {
+                    METRICS.reconnect();
+                }
+                state_tx.send_replace(ConnectionState::Connecting);
+                let mut markets = markets_rx.borrow_and_update().clone();
+                let mut socket = match connect(&source.url, &markets).await {
                     Ok(s) => s,
                     Err(e) => {
-                        log::error!("Connection failed: {e}, retrying in 60s...");
-                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        log::error!(
+                            "Connection failed: {e}, retrying in {}...",
+                            humantime::format_duration(source.reconnect_delay)
+                        );
+                        state_tx.send_replace(ConnectionState::Disconnected(e.to_string()));
+                        tokio::time::sleep(source.reconnect_delay).await;
                         continue;
                     }
                 };
+                state_tx.send_replace(ConnectionState::Connected);
 
+                let mut opens = HashMap::new();
+                let mut request_id = 1;
                 loop {
-                    match recv_price(&mut socket, &markets).await {
+                    let received = tokio::select! {
+                        price = recv_price(&mut socket, &markets, &mut opens) => Some(price),
+                        Ok(()) = markets_rx.changed() => None,
+                    };
+                    let Some(received) = received else {
+                        // follow the new markets on the same connection
+                        let new_markets = markets_rx.borrow_and_update().clone();
+                        let (removed, added) = stream_changes(&markets, &new_markets);
+                        opens.retain(|symbol, _| new_markets.iter().any(|m| &m.symbol == symbol));
+                        markets = new_markets;
+                        match resubscribe(&mut socket, removed, added, &mut request_id).await {
+                            Ok(()) => continue,
+                            Err(error) => {
+                                // reconnecting subscribes to the new markets anyway
+                                log::error!("Failed to resubscribe: {error}, reconnecting...");
+                                state_tx
+                                    .send_replace(ConnectionState::Disconnected(error.to_string()));
+                                break;
+                            }
+                        }
+                    };
+                    match received {
                         Ok(price) => {
+                            METRICS.price_received(&price);
                             if tx.send(price).is_err() {
                                 log::info!("Receiver dropped, exiting background task");
                                 return;
@@ -73,9 +165,11 @@ impl WsCoin {
                         }
                         Err(error) => {
                             log::error!(
-                                "Error happened: {error}, Reconnect in 60s...\n\n\n\n\n\n\n\n",
+                                "Error happened: {error}, Reconnect in {}...",
+                                humantime::format_duration(source.reconnect_delay)
                             );
-                            tokio::time::sleep(Duration::from_secs(60)).await;
+                            state_tx.send_replace(ConnectionState::Disconnected(error.to_string()));
+                            tokio::time::sleep(source.reconnect_delay).await;
                             break;
                         }
                     }
@@ -83,7 +177,24 @@ impl WsCoin {
             }
         });
 
-        WsCoin { rx }
+        WsCoin {
+            rx,
+            state,
+            markets: markets_tx,
+            task,
+        }
+    }
+
+    /// Follow `markets` instead, subscribing to the new ones on the open connection
+    pub fn set_markets(&self, markets: Vec<Market>) {
+        self.markets.send_replace(markets);
+    }
+}
+
+impl Drop for WsCoin {
+    fn drop(&mut self) {
+        // the task may be sleeping before a reconnect, and would only notice on the next price
+        self.task.abort();
     }
 }
 
@@ -96,6 +207,10 @@ impl WsCoin {
         Self::new(markets).await
     }
 
+    pub fn state(&self) -> tokio::sync::watch::Receiver<ConnectionState> {
+        self.state.clone()
+    }
+
     pub fn subscribe(&mut self) -> impl Stream<Item = Price> + '_ {
         async_stream::stream! {
             while let Some(price) = self.rx.recv().await {
@@ -105,15 +220,15 @@ impl WsCoin {
     }
 }
 
-async fn connect(markets: &[Market]) -> anyhow::Result<PriceSocket> {
+async fn connect(url: &str, markets: &[Market]) -> anyhow::Result<PriceSocket> {
     log::info!("Connecting to Binance WebSocket...");
-    let url = Url::parse("wss://stream.binance.com/ws")?;
+    let url = Url::parse(url)?;
     let (mut socket, _) = connect_async(url).await?;
     log::info!("Connected to Binance WebSocket");
 
     let msg = serde_json::json!({
         "method": "SUBSCRIBE",
-        "params": markets.iter().map(|m| format!("{}@aggTrade", m.symbol.to_lowercase())).collect::<Vec<String>>(),
+        "params": streams(markets),
         "id": 1
     })
     .to_string();
@@ -123,7 +238,55 @@ async fn connect(markets: &[Market]) -> anyhow::Result<PriceSocket> {
     Ok(socket)
 }
 
-async fn recv_price(socket: &mut PriceSocket, markets: &[Market]) -> Result<Price, RecvError> {
+/// The streams a market is followed with: its trades, and its 24h ticker for the change
+fn streams(markets: &[Market]) -> Vec<String> {
+    markets
+        .iter()
+        .flat_map(|m| {
+            let symbol = m.symbol.to_lowercase();
+            [format!("{symbol}@aggTrade"), format!("{symbol}@miniTicker")]
+        })
+        .collect()
+}
+
+/// The streams to unsubscribe from and to subscribe to, to follow `new` instead of `old`
+fn stream_changes(old: &[Market], new: &[Market]) -> (Vec<String>, Vec<String>) {
+    let (old, new) = (streams(old), streams(new));
+    let removed = old.iter().filter(|s| !new.contains(s)).cloned().collect();
+    let added = new.iter().filter(|s| !old.contains(s)).cloned().collect();
+    (removed, added)
+}
+
+async fn resubscribe(
+    socket: &mut PriceSocket,
+    removed: Vec<String>,
+    added: Vec<String>,
+    request_id: &mut u8,
+) -> anyhow::Result<()> {
+    for (method, params) in [("UNSUBSCRIBE", removed), ("SUBSCRIBE", added)] {
+        if params.is_empty() {
+            continue;
+        }
+        // 1 is the id of the initial subscription
+        *request_id = request_id.checked_add(1).unwrap_or(2);
+        log::info!("{method} {}", params.join(", "));
+        let msg = serde_json::json!({
+            "method": method,
+            "params": params,
+            "id": request_id
+        })
+        .to_string();
+        socket.send(Message::Text(msg)).await?;
+    }
+    Ok(())
+}
+
+/// Receive the next trade price, keeping track of the price 24h ago of each symbol in `opens`.
+async fn recv_price(
+    socket: &mut PriceSocket,
+    markets: &[Market],
+    opens: &mut HashMap<String, Decimal>,
+) -> Result<Price, RecvError> {
     loop {
         let Some(msg) = tokio::time::timeout(Duration::from_secs(60), socket.next())
             .await
@@ -136,26 +299,31 @@ async fn recv_price(socket: &mut PriceSocket, markets: &[Market]) -> Result<Pric
             Ok(Message::Text(msg)) => match parse_json(&msg) {
                 Ok(msg) => match msg {
                     Msg::Subscribed => log::info!("Subscribed confirmed"),
+                    Msg::Open { symbol, open } => {
+                        opens.insert(symbol, open);
+                    }
                     Msg::Price { symbol, price: p } => {
+                        let Some(market) = markets.iter().find(|m| m.symbol == symbol) else {
+                            // trades may still come in right after unsubscribing
+                            log::debug!("Skipping a trade of {symbol}, which isn't followed");
+                            continue;
+                        };
+                        let change_24h = opens
+                            .get(&symbol)
+                            .filter(|open| !open.is_zero())
+                            .map(|open| ((p - open) / open * Decimal::ONE_HUNDRED).round_dp(2));
                         return Ok(Price {
-                            name: {
-                                let mut name = None;
-                                for market in markets {
-                                    if market.symbol == symbol {
-                                        name = Some(market.name.clone());
-                                        break;
-                                    }
-                                }
-                                name.unwrap_or_else(|| {
-                                    panic!("market name not found for symbol {}", symbol)
-                                })
-                            },
+                            symbol,
+                            name: market.name.clone(),
                             price: p,
-                        })
+                            change_24h,
+                        });
                     }
                 },
                 Err(error) => {
-                    panic!("Error {} happened parsing json: {}", error, &msg)
+                    // one odd message shouldn't take the display down
+                    log::error!("Error {} happened parsing json: {}", error, &msg);
+                    METRICS.parse_error();
                 }
             },
             Ok(Message::Ping(data)) => {
@@ -174,3 +342,21 @@ async fn recv_price(socket: &mut PriceSocket, markets: &[Market]) -> Result<Pric
         }
     }
 }
+
+#[test]
+fn test_stream_changes() {
+    let market = |symbol: &str| Market {
+        symbol: symbol.to_string(),
+        name: symbol[..3].to_string(),
+    };
+    let (removed, added) = stream_changes(
+        &[market("BTCUSDT"), market("ETHUSDT")],
+        &[market("BTCUSDT"), market("SOLUSDT")],
+    );
+    assert_eq!(removed, ["ethusdt@aggTrade", "ethusdt@miniTicker"]);
+    assert_eq!(added, ["solusdt@aggTrade", "solusdt@miniTicker"]);
+    assert_eq!(
+        stream_changes(&[market("BTCUSDT")], &[market("BTCUSDT")]),
+        (vec![], vec![])
+    );
+}
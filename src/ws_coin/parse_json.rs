@@ -6,7 +6,15 @@ use serde::Deserialize;
 #[derive(Debug)]
 pub enum Msg {
     Subscribed,
-    Price { symbol: String, price: Decimal },
+    Price {
+        symbol: String,
+        price: Decimal,
+    },
+    /// Price 24h ago, from the rolling mini ticker
+    Open {
+        symbol: String,
+        open: Decimal,
+    },
 }
 
 #[derive(Deserialize, PartialEq, Debug)]
@@ -21,6 +29,12 @@ enum Received {
     Subscribed {
         id: u8,
     },
+    MiniTicker {
+        #[serde(rename = "s")]
+        symbol: String,
+        #[serde(rename = "o")]
+        open: String,
+    },
 }
 
 pub fn parse_json(data: &str) -> Result<Msg, Box<dyn Error>> {
@@ -30,6 +44,10 @@ pub fn parse_json(data: &str) -> Result<Msg, Box<dyn Error>> {
             symbol,
             price: price.parse::<Decimal>()?.normalize(),
         },
+        Received::MiniTicker { symbol, open } => Msg::Open {
+            symbol,
+            open: open.parse::<Decimal>()?.normalize(),
+        },
     })
 }
 
@@ -54,6 +72,17 @@ fn test_parse_json() {
                 "T": 123456785,
                 "m": true,
                 "M": true
+            },
+            {
+                "e": "24hrMiniTicker",
+                "E": 123456789,
+                "s": "BNBBTC",
+                "c": "0.0025",
+                "o": "0.0010",
+                "h": "0.0025",
+                "l": "0.0010",
+                "v": "10000",
+                "q": "18"
             }
         ]
         "#,
@@ -68,6 +97,10 @@ fn test_parse_json() {
                 symbol: "BNBBTC".to_string(),
                 price: "0.001".to_string(),
             },
+            Received::MiniTicker {
+                symbol: "BNBBTC".to_string(),
+                open: "0.0010".to_string(),
+            },
         ]
     );
 }